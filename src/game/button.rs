@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{Collider, Sensor};
+
+use crate::game::{interactable::{Clicked, Interactable}, states::GameplaySet};
+
+pub struct ButtonPlugin;
+impl Plugin for ButtonPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_observer(on_button_clicked);
+        app.add_systems(Update, release_buttons.in_set(GameplaySet));
+    }
+}
+
+const BUTTON_SIZE: Vec2 = vec2(32.0, 32.0);
+const BUTTON_COLOR: Color = Color::linear_rgb(0.8, 0.1, 0.1);
+const BUTTON_PRESSED_COLOR: Color = Color::linear_rgb(0.4, 0.05, 0.05);
+const BUTTON_PRESSED_SCALE: f32 = 0.8;
+const BUTTON_PRESS_SECS: f32 = 0.15;
+
+#[derive(Component)]
+pub struct ClickAction(pub Box<dyn Fn(&mut Commands) + Send + Sync>);
+
+// a button that was just clicked, shown pushed in until the timer runs out
+#[derive(Component)]
+struct ButtonPressed(Timer);
+
+pub fn spawn_any_button<M: Message + Clone>(
+    cmds: &mut Commands,
+    pos: Vec2,
+    message: M
+) -> Entity {
+    cmds.spawn((
+        Interactable,
+        ClickAction(Box::new(move |cmds| {
+            // This sends the specific message type M
+            cmds.write_message(message.clone());
+        })),
+        Transform::from_xyz(pos.x, pos.y, 0.0),
+        Sprite::from_color(BUTTON_COLOR, BUTTON_SIZE),
+        Collider::cuboid(BUTTON_SIZE.x/2.0, BUTTON_SIZE.y/2.0),
+        Sensor,
+    )).id()
+}
+
+fn on_button_clicked(
+    clicked: On<Clicked>,
+    mut cmds: Commands,
+    click_action_q: Query<&ClickAction>,
+){
+    let Ok(click_action) = click_action_q.get(clicked.target) else {return};
+    (click_action.0)(&mut cmds);
+    cmds.entity(clicked.target).insert(ButtonPressed(Timer::from_seconds(BUTTON_PRESS_SECS, TimerMode::Once)));
+}
+
+fn release_buttons(
+    mut cmds: Commands,
+    time: Res<Time>,
+    mut button_q: Query<(Entity, &mut ButtonPressed, &mut Sprite, &mut Transform)>,
+){
+    for (button_entity, mut button_pressed, mut sprite, mut transform) in button_q.iter_mut(){
+        if button_pressed.is_added() {
+            sprite.color = BUTTON_PRESSED_COLOR;
+            transform.scale = Vec3::splat(BUTTON_PRESSED_SCALE);
+        }
+        if !button_pressed.0.tick(time.delta()).is_finished() {continue;}
+        sprite.color = BUTTON_COLOR;
+        transform.scale = Vec3::ONE;
+        cmds.entity(button_entity).remove::<ButtonPressed>();
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::BTreeMap;
+
+use bevy::{asset::{AssetLoader, LoadContext, io::Reader}, platform::collections::{HashMap, HashSet}, prelude::*};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::game::{input::{ActionState, InputAction, WorldInputSet}, interactable::Clicked, npc::{NPC, Quest, Reward}, score::{RewardGranted, SessionStats, Wallet}, states::GameState};
+
+pub struct DialoguePlugin;
+impl Plugin for DialoguePlugin{
+    fn build(&self, app: &mut App) {
+        app.init_asset::<DialogueAsset>();
+        app.register_asset_loader(DialogueLoader);
+        app.init_resource::<DialogueMemory>();
+        app.add_observer(on_npc_clicked);
+        app.add_systems(OnEnter(GameState::InGame), reset_dialogue_memory);
+        // clicks meant for the dialogue should not grab or use things behind it
+        app.configure_sets(Update, WorldInputSet.run_if(not(resource_exists::<ActiveDialogue>)));
+        app.add_systems(Update, (
+            handle_dialogue_buttons,
+            continue_dialogue,
+            close_orphaned_dialogue,
+            update_dialogue_box,
+        ).chain().after(WorldInputSet));
+    }
+}
+
+const DEFAULT_START_NODE: &str = "start";
+const DIALOGUE_BACKGROUND_COLOR: Color = Color::srgba(0.05, 0.0, 0.1, 0.9);
+const DIALOGUE_NAME_COLOR: Color = Color::srgb(1.0, 0.8, 0.3);
+const DIALOGUE_BUTTON_COLOR: Color = Color::srgb(0.2, 0.1, 0.3);
+const DIALOGUE_BUTTON_HOVER_COLOR: Color = Color::srgb(0.4, 0.2, 0.5);
+const DIALOGUE_CONTINUE_TEXT: &str = "...";
+
+// the dialogue an NPC starts when clicked
+#[derive(Component)]
+pub struct Dialogue(pub Handle<DialogueAsset>);
+
+// nodes each NPC has already shown this session, so writers can make things happen only once.
+// keyed by name, a returning customer is a new entity
+#[derive(Resource, Default)]
+pub struct DialogueMemory(pub HashMap<String, HashSet<String>>);
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct DialogueAsset {
+    #[serde(default = "default_start_node")]
+    pub start: String,
+    pub nodes: BTreeMap<String, DialogueNode>,
+}
+
+fn default_start_node() -> String {DEFAULT_START_NODE.to_string()}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DialogueNode {
+    // shown one after another, the choices come with the last line
+    pub lines: Vec<String>,
+    // run when the node is entered
+    #[serde(default)]
+    pub actions: Vec<DialogueAction>,
+    // choices whose conditions are not met are hidden, without any the dialogue ends
+    #[serde(default)]
+    pub choices: Vec<DialogueChoice>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DialogueChoice {
+    pub text: String,
+    #[serde(default)]
+    pub conditions: Vec<DialogueCondition>,
+    #[serde(default)]
+    pub actions: Vec<DialogueAction>,
+    // ends the dialogue when missing
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum DialogueCondition {
+    PointsAtLeast(f32),
+    KeysAtLeast(u32),
+    OrdersServedAtLeast(u32),
+    // an item with this name was handed to any customer this session
+    Delivered(String),
+    // the NPC is currently waiting for an item
+    HasQuest,
+    Visited(String),
+    Not(Box<DialogueCondition>),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum DialogueAction {
+    // replaces what the NPC currently wants
+    GiveQuest { wants: String, reward: Reward },
+    GiveReward(Reward),
+}
+
+struct DialogueContext<'a> {
+    wallet: &'a Wallet,
+    session_stats: &'a SessionStats,
+    // nodes this NPC has already shown
+    visited: Option<&'a HashSet<String>>,
+    has_quest: bool,
+}
+
+impl DialogueCondition {
+    fn is_met(&self, context: &DialogueContext) -> bool {
+        match self {
+            DialogueCondition::PointsAtLeast(points) => context.wallet.points >= *points,
+            DialogueCondition::KeysAtLeast(keys) => context.wallet.keys >= *keys,
+            DialogueCondition::OrdersServedAtLeast(orders) => context.session_stats.orders_served >= *orders,
+            DialogueCondition::Delivered(item) => context.session_stats.delivered_items.contains_key(item),
+            DialogueCondition::HasQuest => context.has_quest,
+            DialogueCondition::Visited(node) => context.visited.is_some_and(|visited| visited.contains(node)),
+            DialogueCondition::Not(condition) => !condition.is_met(context),
+        }
+    }
+}
+
+impl DialogueChoice {
+    fn is_available(&self, context: &DialogueContext) -> bool {
+        self.conditions.iter().all(|condition| condition.is_met(context))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DialogueLoadError {
+    #[error("could not read dialogue: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse dialogue: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid dialogue node \"{node}\": {reason}")]
+    InvalidNode { node: String, reason: String },
+}
+
+impl DialogueAsset {
+    fn validate(&self) -> Result<(), DialogueLoadError> {
+        if !self.nodes.contains_key(&self.start) {
+            return Err(DialogueLoadError::InvalidNode {
+                node: self.start.clone(),
+                reason: "start node does not exist".to_string(),
+            });
+        }
+        for (name, node) in self.nodes.iter(){
+            let invalid = |reason: String| DialogueLoadError::InvalidNode {
+                node: name.clone(),
+                reason,
+            };
+
+            if node.lines.is_empty() {
+                return Err(invalid("needs at least one line".to_string()));
+            }
+            for choice in node.choices.iter(){
+                if let Some(next) = &choice.next {
+                    if !self.nodes.contains_key(next) {
+                        return Err(invalid(format!("choice \"{}\" leads to unknown node \"{}\"", choice.text, next)));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct DialogueLoader;
+
+impl AssetLoader for DialogueLoader {
+    type Asset = DialogueAsset;
+    type Settings = ();
+    type Error = DialogueLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let dialogue = ron::de::from_bytes::<DialogueAsset>(&bytes)?;
+        dialogue.validate()?;
+        Ok(dialogue)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dialogue.ron"]
+    }
+}
+
+// the conversation that is currently on screen, only one at a time
+#[derive(Resource)]
+pub struct ActiveDialogue {
+    pub npc: Entity,
+    npc_name: String,
+    dialogue: Handle<DialogueAsset>,
+    node: String,
+    line: usize,
+}
+
+#[derive(Component)]
+struct DialogueBox;
+
+#[derive(Component)]
+enum DialogueButton {
+    Continue,
+    Choice(usize),
+}
+
+fn on_npc_clicked(
+    clicked: On<Clicked>,
+    mut cmds: Commands,
+    active_dialogue: Option<Res<ActiveDialogue>>,
+    dialogue_q: Query<(&Dialogue, &NPC)>,
+    dialogue_assets: Res<Assets<DialogueAsset>>,
+    mut dialogue_memory: ResMut<DialogueMemory>,
+    mut reward_granted_msgs: MessageWriter<RewardGranted>,
+){
+    if active_dialogue.is_some() {return;}
+    let Ok((dialogue, npc)) = dialogue_q.get(clicked.target) else {return};
+    let Some(dialogue_asset) = dialogue_assets.get(&dialogue.0) else {return};
+
+    enter_node(&mut cmds, clicked.target, &npc.name, dialogue_asset, &dialogue_asset.start, &mut dialogue_memory, &mut reward_granted_msgs);
+    cmds.insert_resource(ActiveDialogue {
+        npc: clicked.target,
+        npc_name: npc.name.clone(),
+        dialogue: dialogue.0.clone(),
+        node: dialogue_asset.start.clone(),
+        line: 0,
+    });
+}
+
+fn enter_node(
+    cmds: &mut Commands,
+    npc: Entity,
+    npc_name: &str,
+    dialogue_asset: &DialogueAsset,
+    node_name: &str,
+    dialogue_memory: &mut DialogueMemory,
+    reward_granted_msgs: &mut MessageWriter<RewardGranted>,
+){
+    let Some(node) = dialogue_asset.nodes.get(node_name) else {return};
+    dialogue_memory.0.entry(npc_name.to_string()).or_default().insert(node_name.to_string());
+    run_actions(cmds, npc, &node.actions, reward_granted_msgs);
+}
+
+fn run_actions(
+    cmds: &mut Commands,
+    npc: Entity,
+    actions: &[DialogueAction],
+    reward_granted_msgs: &mut MessageWriter<RewardGranted>,
+){
+    for action in actions.iter(){
+        match action {
+            DialogueAction::GiveQuest { wants, reward } => {
+                cmds.entity(npc).insert(Quest { wants: wants.clone(), reward: reward.clone() });
+            }
+            DialogueAction::GiveReward(reward) => {
+                reward_granted_msgs.write(RewardGranted(reward.clone()));
+            }
+        }
+    }
+}
+
+fn handle_dialogue_buttons(
+    mut cmds: Commands,
+    active_dialogue: Option<ResMut<ActiveDialogue>>,
+    mut button_q: Query<(&Interaction, &DialogueButton, &mut BackgroundColor), Changed<Interaction>>,
+    dialogue_assets: Res<Assets<DialogueAsset>>,
+    mut dialogue_memory: ResMut<DialogueMemory>,
+    mut reward_granted_msgs: MessageWriter<RewardGranted>,
+){
+    let Some(mut active_dialogue) = active_dialogue else {return};
+    let mut pressed = None;
+    for (interaction, dialogue_button, mut background_color) in button_q.iter_mut(){
+        background_color.0 = match interaction {
+            Interaction::Hovered => DIALOGUE_BUTTON_HOVER_COLOR,
+            _ => DIALOGUE_BUTTON_COLOR,
+        };
+        if *interaction == Interaction::Pressed {
+            pressed = Some(dialogue_button);
+        }
+    }
+    let Some(pressed) = pressed else {return};
+    let Some(dialogue_asset) = dialogue_assets.get(&active_dialogue.dialogue) else {return};
+
+    match pressed {
+        DialogueButton::Continue => {
+            advance_line(&mut cmds, &mut active_dialogue, dialogue_asset);
+        }
+        DialogueButton::Choice(index) => {
+            let Some(choice) = dialogue_asset.nodes.get(&active_dialogue.node)
+                .and_then(|node| node.choices.get(*index))
+            else {return};
+            let npc = active_dialogue.npc;
+            run_actions(&mut cmds, npc, &choice.actions, &mut reward_granted_msgs);
+            let Some(next) = &choice.next else {
+                cmds.remove_resource::<ActiveDialogue>();
+                return;
+            };
+            enter_node(&mut cmds, npc, &active_dialogue.npc_name, dialogue_asset, next, &mut dialogue_memory, &mut reward_granted_msgs);
+            active_dialogue.node = next.clone();
+            active_dialogue.line = 0;
+        }
+    }
+}
+
+// the interact action skips through lines, picking a choice needs the buttons
+fn continue_dialogue(
+    mut cmds: Commands,
+    action_state: Res<ActionState>,
+    active_dialogue: Option<ResMut<ActiveDialogue>>,
+    dialogue_assets: Res<Assets<DialogueAsset>>,
+    choice_q: Query<&DialogueButton>,
+){
+    if !action_state.just_pressed(InputAction::Interact) {return;}
+    let Some(mut active_dialogue) = active_dialogue else {return};
+    // the same press that opened the dialogue should not skip its first line
+    if active_dialogue.is_added() {return;}
+    if choice_q.iter().any(|dialogue_button| matches!(dialogue_button, DialogueButton::Choice(_))) {return;}
+    let Some(dialogue_asset) = dialogue_assets.get(&active_dialogue.dialogue) else {return};
+    advance_line(&mut cmds, &mut active_dialogue, dialogue_asset);
+}
+
+fn advance_line(
+    cmds: &mut Commands,
+    active_dialogue: &mut ActiveDialogue,
+    dialogue_asset: &DialogueAsset,
+){
+    let Some(node) = dialogue_asset.nodes.get(&active_dialogue.node) else {
+        cmds.remove_resource::<ActiveDialogue>();
+        return;
+    };
+    if active_dialogue.line +1 < node.lines.len() {
+        active_dialogue.line += 1;
+    } else {
+        cmds.remove_resource::<ActiveDialogue>();
+    }
+}
+
+// customers can walk off in the middle of a conversation
+fn reset_dialogue_memory(
+    mut dialogue_memory: ResMut<DialogueMemory>,
+){
+    dialogue_memory.0.clear();
+}
+
+fn close_orphaned_dialogue(
+    mut cmds: Commands,
+    active_dialogue: Option<Res<ActiveDialogue>>,
+    npc_q: Query<(), With<NPC>>,
+){
+    let Some(active_dialogue) = active_dialogue else {return};
+    if !npc_q.contains(active_dialogue.npc) {
+        cmds.remove_resource::<ActiveDialogue>();
+    }
+}
+
+fn update_dialogue_box(
+    mut cmds: Commands,
+    active_dialogue: Option<Res<ActiveDialogue>>,
+    dialogue_box_q: Query<Entity, With<DialogueBox>>,
+    dialogue_assets: Res<Assets<DialogueAsset>>,
+    npc_q: Query<(&NPC, Has<Quest>)>,
+    dialogue_memory: Res<DialogueMemory>,
+    wallet: Res<Wallet>,
+    session_stats: Res<SessionStats>,
+){
+    let changed = active_dialogue.as_ref().is_none_or(|active_dialogue| active_dialogue.is_changed());
+    if !changed {return;}
+    for dialogue_box in dialogue_box_q.iter(){
+        cmds.entity(dialogue_box).despawn();
+    }
+
+    let Some(active_dialogue) = active_dialogue else {return};
+    let Some(dialogue_asset) = dialogue_assets.get(&active_dialogue.dialogue) else {return};
+    let Some(node) = dialogue_asset.nodes.get(&active_dialogue.node) else {return};
+    let Some(line) = node.lines.get(active_dialogue.line) else {return};
+    let Ok((npc, has_quest)) = npc_q.get(active_dialogue.npc) else {return};
+
+    let context = DialogueContext {
+        wallet: &wallet,
+        session_stats: &session_stats,
+        visited: dialogue_memory.0.get(&npc.name),
+        has_quest,
+    };
+    let is_last_line = active_dialogue.line +1 >= node.lines.len();
+    let mut buttons: Vec<(DialogueButton, String)> = Vec::new();
+    if is_last_line {
+        buttons.extend(node.choices.iter().enumerate()
+            .filter(|(_, choice)| choice.is_available(&context))
+            .map(|(index, choice)| (DialogueButton::Choice(index), choice.text.clone())));
+    }
+    if buttons.is_empty() {
+        buttons.push((DialogueButton::Continue, DIALOGUE_CONTINUE_TEXT.to_string()));
+    }
+
+    cmds.spawn((
+        DialogueBox,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(16.0),
+            right: Val::Px(16.0),
+            bottom: Val::Px(16.0),
+            padding: UiRect::all(Val::Px(12.0)),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        BackgroundColor(DIALOGUE_BACKGROUND_COLOR),
+    )).with_children(|parent| {
+        parent.spawn((Text::new(npc.name.clone()), TextColor(DIALOGUE_NAME_COLOR)));
+        parent.spawn(Text::new(line.clone()));
+        for (dialogue_button, text) in buttons{
+            parent.spawn((
+                dialogue_button,
+                Button,
+                Node {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                    ..default()
+                },
+                BackgroundColor(DIALOGUE_BUTTON_COLOR),
+                children![Text::new(text)],
+            ));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(source: &str) -> DialogueCondition {
+        ron::de::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn conditions_read_wallet_and_session_stats() {
+        let wallet = Wallet { points: 10.0, keys: 1 };
+        let mut session_stats = SessionStats { orders_served: 2, ..default() };
+        session_stats.delivered_items.insert("yellow".to_string(), 1);
+        let context = DialogueContext { wallet: &wallet, session_stats: &session_stats, visited: None, has_quest: false };
+
+        assert!(condition("PointsAtLeast(10.0)").is_met(&context));
+        assert!(!condition("PointsAtLeast(10.5)").is_met(&context));
+        assert!(condition("KeysAtLeast(1)").is_met(&context));
+        assert!(!condition("KeysAtLeast(2)").is_met(&context));
+        assert!(condition("OrdersServedAtLeast(2)").is_met(&context));
+        assert!(!condition("OrdersServedAtLeast(3)").is_met(&context));
+        assert!(condition(r#"Delivered("yellow")"#).is_met(&context));
+        assert!(!condition(r#"Delivered("violet")"#).is_met(&context));
+        assert!(!condition("HasQuest").is_met(&context));
+    }
+
+    #[test]
+    fn visited_checks_the_npc_memory() {
+        let wallet = Wallet::default();
+        let session_stats = SessionStats::default();
+        let visited: HashSet<String> = ["start".to_string()].into_iter().collect();
+        let remembering = DialogueContext { wallet: &wallet, session_stats: &session_stats, visited: Some(&visited), has_quest: true };
+        let forgetful = DialogueContext { visited: None, ..remembering };
+
+        assert!(condition(r#"Visited("start")"#).is_met(&remembering));
+        assert!(!condition(r#"Visited("breathing")"#).is_met(&remembering));
+        assert!(!condition(r#"Visited("start")"#).is_met(&forgetful));
+        assert!(condition(r#"Not(Visited("start"))"#).is_met(&forgetful));
+        assert!(condition("HasQuest").is_met(&forgetful));
+    }
+
+    #[test]
+    fn choices_need_every_condition() {
+        let wallet = Wallet { points: 5.0, keys: 0 };
+        let session_stats = SessionStats::default();
+        let context = DialogueContext { wallet: &wallet, session_stats: &session_stats, visited: None, has_quest: false };
+        let choice = |source: &str| ron::de::from_str::<DialogueChoice>(source).unwrap();
+
+        assert!(choice(r#"(text: "hi")"#).is_available(&context));
+        assert!(choice(r#"(text: "hi", conditions: [PointsAtLeast(5.0), Not(HasQuest)])"#).is_available(&context));
+        assert!(!choice(r#"(text: "hi", conditions: [PointsAtLeast(5.0), KeysAtLeast(1)])"#).is_available(&context));
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::game::{machine::MachineHull, states::GameplaySet};
+
+pub struct HammerPlugin;
+impl Plugin for HammerPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_message::<MachineHit>();
+        app.add_systems(Update, detect_hammer_hits.in_set(GameplaySet));
+    }
+}
+
+// contact force needed before a hammer touching a machine counts as a hit
+const HAMMER_HIT_FORCE: f32 = 4000.0;
+const HAMMER_HIT_COOLDOWN_SECS: f32 = 0.25;
+
+#[derive(Component)]
+pub struct Hammer{
+    last_hit_secs: f32,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct MachineHit{
+    pub machine: Entity,
+    pub force: f32,
+}
+
+pub fn spawn_hammer(cmds: &mut Commands, pos: Vec2) -> Entity{
+    cmds.spawn((
+        Hammer {last_hit_secs: f32::NEG_INFINITY},
+        Sprite::from_color(Color::linear_rgb(0.4, 0.25, 0.1), vec2(8.0, 80.0)),
+        Transform::from_xyz(pos.x, pos.y, 1.0),
+        RigidBody::Dynamic,
+        Collider::compound(vec![
+            (vec2(0.0, 0.0), 0.0, Collider::cuboid(4.0, 40.0)),
+            (vec2(0.0, 40.0), 0.0, Collider::cuboid(20.0, 10.0)),
+        ]),
+        ColliderMassProperties::Density(3.0),
+        ActiveEvents::CONTACT_FORCE_EVENTS,
+        ContactForceEventThreshold(HAMMER_HIT_FORCE),
+        children![(
+            Sprite::from_color(Color::linear_rgb(0.5, 0.5, 0.55), vec2(40.0, 20.0)),
+            Transform::from_xyz(0.0, 40.0, 0.1),
+        )],
+    )).id()
+}
+
+fn detect_hammer_hits(
+    time: Res<Time>,
+    mut contact_force_msgs: MessageReader<ContactForceEvent>,
+    mut hammer_q: Query<&mut Hammer>,
+    machine_hull_q: Query<&MachineHull>,
+    mut machine_hit_msgs: MessageWriter<MachineHit>,
+){
+    for contact in contact_force_msgs.read(){
+        let (hammer_entity, other_entity) = if hammer_q.contains(contact.collider1) {
+            (contact.collider1, contact.collider2)
+        } else if hammer_q.contains(contact.collider2) {
+            (contact.collider2, contact.collider1)
+        } else {
+            continue;
+        };
+        let Ok(machine_hull) = machine_hull_q.get(other_entity) else {continue;};
+        let Ok(mut hammer) = hammer_q.get_mut(hammer_entity) else {continue;};
+
+        // a resting hammer keeps reporting contact forces, only count one hit per cooldown
+        let now = time.elapsed_secs();
+        if now -hammer.last_hit_secs < HAMMER_HIT_COOLDOWN_SECS {continue;}
+        hammer.last_hit_secs = now;
+
+        machine_hit_msgs.write(MachineHit {
+            machine: machine_hull.0,
+            force: contact.total_force_magnitude,
+        });
+    }
+}
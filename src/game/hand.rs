@@ -1,16 +1,20 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-use crate::game::{interactable::{Interactable, InteractionType}, states::GameState};
+use crate::game::{input::{ActionState, GAMEPAD_STICK_DEADZONE, InputAction, InputDevice, LastInputDevice, WorldInputSet}, interactable::Interactable, item::Item, rooms::ROOM_SIZE, states::{GameState, GameplaySet}, touch::TouchState};
 
 pub struct HandPlugin;
 impl Plugin for HandPlugin{
     fn build(&self, app: &mut App) {
         app
-        .configure_sets(Update, HandUpdateSet)
+        .init_resource::<HandSettings>()
+        .add_message::<GrabReleased>()
+        .add_message::<ItemGrabbed>()
+        .add_message::<ItemReleased>()
+        .configure_sets(Update, HandUpdateSet.in_set(GameplaySet))
         .add_systems(Startup, setup_hand)
-        .add_systems(Update, handle_grabbing.in_set(HandUpdateSet))
-        .add_systems(PostUpdate, handle_movement)
+        .add_systems(Update, (release_missing_body, handle_grabbing.in_set(WorldInputSet), raise_grabbed_body, rotate_grabbed_body, drive_grab_joint, report_item_grabs).chain().in_set(HandUpdateSet))
+        .add_systems(PostUpdate, handle_movement.in_set(GameplaySet))
         ;
     }
 }
@@ -18,11 +22,110 @@ impl Plugin for HandPlugin{
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct HandUpdateSet;
 
+const HAND_VELOCITY_SAMPLES: usize = 5;
+pub const HAND_RADIUS: f32 = 5.0;
+// grabbed bodies are moved up so the latest one is on top of a pile
+const GRAB_BASE_Z: f32 = 1.0;
+const GRAB_Z_STEP: f32 = 0.001;
+// after this many grabs the raised bodies are packed back down so they stay below the item labels
+const GRAB_RAISE_LIMIT: u32 = 1000;
+const GRAB_ROTATE_STIFFNESS: f32 = 1.0;
+const GRAB_ROTATE_DAMPING: f32 = 5.0;
+// used until rapier has computed the mass of a freshly grabbed body
+const GRAB_FALLBACK_MASS: f32 = 1.0;
+// radians per second while a rotate button is held
+const GRAB_ROTATE_SPEED: f32 = 3.0;
+// radians per mouse wheel notch
+const GRAB_ROTATE_STEP: f32 = 0.2;
+
+// bodies with this can never be picked up by the hand, e.g. machine hulls
+#[derive(Component)]
+pub struct NotGrabbable;
+
 #[derive(Component)]
 pub struct Hand {
     pub is_grabbing: bool,
     pub grab_joint_entity: Option<Entity>,
     pub grabbed_body: Option<Entity>,
+    // averaged over the last few frames so a throw does not depend on a single jittery frame
+    pub velocity: Vec2,
+    velocity_samples: Vec<Vec2>,
+    // speed of the gamepad driven cursor, ramps up while the stick is held
+    cursor_speed: f32,
+    // angle the grab joint motor turns the held body to
+    pub target_angle: f32,
+    // where the hand holds the grabbed body, in the bodys local space
+    grab_anchor: Vec2,
+    // mass the grab spring is currently tuned for
+    grab_mass: f32,
+    // ccd was switched on for the grab and goes off again on release
+    grab_added_ccd: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrabReleaseReason{
+    // the grab button was let go
+    Released,
+    // the body was pulled harder than the hand can hold
+    GripBroken,
+    // the body was despawned or stopped being a rigid body while held, e.g. put into a machine
+    BodyRemoved,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct GrabReleased{
+    pub body: Entity,
+    pub reason: GrabReleaseReason,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct ItemGrabbed{
+    pub item: Entity,
+    pub name: String,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct ItemReleased{
+    pub item: Entity,
+    pub name: String,
+    pub reason: GrabReleaseReason,
+}
+
+#[derive(Resource)]
+pub struct HandSettings {
+    pub max_throw_speed: f32,
+    pub gamepad_cursor_min_speed: f32,
+    pub gamepad_cursor_max_speed: f32,
+    pub gamepad_cursor_acceleration: f32,
+    // with the stick released the cursor drifts onto things within this radius
+    pub gamepad_snap_radius: f32,
+    pub gamepad_snap_strength: f32,
+    // grab spring stiffness for a body of mass 1, heavier bodies get sqrt(mass) times this
+    // so they accelerate slower and trail behind the hand
+    pub grab_stiffness: f32,
+    // 1.0 is critically damped
+    pub grab_damping_ratio: f32,
+    // the most force the hand can pull with, keeps held items from being forced through walls
+    pub grab_max_force: f32,
+    // the grip breaks once the spring would have to pull harder than this
+    pub grab_break_force: f32,
+}
+
+impl Default for HandSettings {
+    fn default() -> Self {
+        HandSettings {
+            max_throw_speed: 1500.0,
+            gamepad_cursor_min_speed: 200.0,
+            gamepad_cursor_max_speed: 1200.0,
+            gamepad_cursor_acceleration: 1500.0,
+            gamepad_snap_radius: 48.0,
+            gamepad_snap_strength: 10.0,
+            grab_stiffness: 400.0,
+            grab_damping_ratio: 0.8,
+            grab_max_force: 20000.0,
+            grab_break_force: 40000.0,
+        }
+    }
 }
 
 fn setup_hand(
@@ -32,95 +135,404 @@ fn setup_hand(
         Hand {
             is_grabbing: false,
             grab_joint_entity: None,
-            grabbed_body: None
+            grabbed_body: None,
+            velocity: Vec2::ZERO,
+            velocity_samples: Vec::with_capacity(HAND_VELOCITY_SAMPLES),
+            cursor_speed: 0.0,
+            target_angle: 0.0,
+            grab_anchor: Vec2::ZERO,
+            grab_mass: GRAB_FALLBACK_MASS,
+            grab_added_ccd: false,
         },
         Sprite::from_color(Color::linear_rgb(0.1, 0.1, 0.1), vec2(5.0, 5.0)),
         Transform::from_xyz(0.0, 0.0, 0.0),
         RigidBody::KinematicPositionBased,
-        Collider::ball(5.0),
+        Collider::ball(HAND_RADIUS),
         Sensor,
     ));
 }
 
 fn handle_movement(
-    mut hand_q: Query<&mut Transform, With<Hand>>,
+    mut hand_q: Query<(&mut Hand, &mut Transform)>,
     
     camera_query: Query<(&Camera, &GlobalTransform)>,
     windows: Query<&Window>,
+    time: Res<Time>,
+    last_input_device: Res<LastInputDevice>,
+    hand_settings: Res<HandSettings>,
+    gamepad_q: Query<&Gamepad>,
+    touch_state: Res<TouchState>,
+    snap_target_q: Query<(Entity, &Transform), (Or<(With<Item>, With<Interactable>)>, Without<Hand>)>,
 
 ){
     let Ok((camera, camera_transform)) = camera_query.single() else { return };
     let Ok(window) = windows.single() else { return };
 
-    if let Some(cursor_pos) = window.cursor_position() {
-        if let Ok(world_pos) = camera.viewport_to_world_2d(camera_transform, cursor_pos) {
-            for mut hand_transform in hand_q.iter_mut(){
-                hand_transform.translation.x = world_pos.x;
-                hand_transform.translation.y = world_pos.y;
+    let world_pos = window.cursor_position()
+        .and_then(|cursor_pos| camera.viewport_to_world_2d(camera_transform, cursor_pos).ok());
+    let stick = gamepad_q.iter()
+        .map(|gamepad| gamepad.left_stick())
+        .find(|stick| stick.length() > GAMEPAD_STICK_DEADZONE);
+
+    for (mut hand, mut hand_transform) in hand_q.iter_mut(){
+        let old_pos = hand_transform.translation.truncate();
+        let new_pos = match last_input_device.0 {
+            InputDevice::Mouse => world_pos,
+            InputDevice::Touch => touch_state.position
+                .and_then(|touch_pos| camera.viewport_to_world_2d(camera_transform, touch_pos).ok()),
+            InputDevice::Gamepad => {
+                let snap_targets = snap_target_q.iter()
+                    .filter(|(entity, _)| Some(*entity) != hand.grabbed_body)
+                    .map(|(_, transform)| transform.translation.truncate());
+                let view_center = camera_transform.translation().truncate();
+                let pos = move_virtual_cursor(&mut hand, old_pos, stick, snap_targets, &hand_settings, time.delta_secs());
+                Some(pos.clamp(view_center -ROOM_SIZE/2.0, view_center +ROOM_SIZE/2.0))
             }
+        };
+        if let Some(new_pos) = new_pos {
+            hand_transform.translation.x = new_pos.x;
+            hand_transform.translation.y = new_pos.y;
         }
+
+        if time.delta_secs() > 0.0 {
+            let frame_velocity = (hand_transform.translation.truncate() - old_pos) / time.delta_secs();
+            if hand.velocity_samples.len() >= HAND_VELOCITY_SAMPLES {
+                hand.velocity_samples.remove(0);
+            }
+            hand.velocity_samples.push(frame_velocity);
+            hand.velocity = hand.velocity_samples.iter().sum::<Vec2>() / hand.velocity_samples.len() as f32;
+        }
+    }
+
+}
+
+fn move_virtual_cursor(
+    hand: &mut Hand,
+    hand_pos: Vec2,
+    stick: Option<Vec2>,
+    snap_targets: impl Iterator<Item = Vec2>,
+    hand_settings: &HandSettings,
+    delta_secs: f32,
+) -> Vec2{
+    if let Some(stick) = stick {
+        hand.cursor_speed = (hand.cursor_speed.max(hand_settings.gamepad_cursor_min_speed)
+            + hand_settings.gamepad_cursor_acceleration * delta_secs)
+            .min(hand_settings.gamepad_cursor_max_speed);
+        return hand_pos +stick.clamp_length_max(1.0) * hand.cursor_speed * delta_secs;
+    }
+    hand.cursor_speed = 0.0;
+
+    let snap_target = snap_targets
+        .filter(|target| target.distance(hand_pos) < hand_settings.gamepad_snap_radius)
+        .min_by(|a, b| a.distance(hand_pos).total_cmp(&b.distance(hand_pos)));
+    match snap_target {
+        Some(snap_target) => hand_pos.lerp(snap_target, (hand_settings.gamepad_snap_strength * delta_secs).min(1.0)),
+        None => hand_pos,
     }
+}
+
+pub type GrabbableQuery<'w, 's> = Query<'w, 's, (Entity, &'static Transform, &'static RigidBody), (With<Collider>, Without<NotGrabbable>)>;
+
+// the body a click at `hand_pos` would pick up, shared with the hover feedback.
+// picks the topmost dynamic body and breaks ties by distance to the hand
+pub fn find_grab_target(
+    rapier_context: &RapierContext,
+    hand_entity: Entity,
+    hand_pos: Vec2,
+    rigidbody_query: &GrabbableQuery,
+) -> Option<Entity>{
+    let mut candidates = Vec::<(Entity, f32, f32)>::new();
+    rapier_context.intersect_shape(
+        hand_pos, 
+        0.0, 
+        Collider::ball(HAND_RADIUS).raw.make_mut(), 
+        QueryFilter::default(),
+        | entity| {
+            if hand_entity == entity{return true;}
+
+            if let Ok((_, rigidbody_transform, rigidbody)) = rigidbody_query.get(entity) {
+                if matches!(rigidbody, RigidBody::Dynamic){
+                    let distance = rigidbody_transform.translation.truncate().distance(hand_pos);
+                    candidates.push((entity, rigidbody_transform.translation.z, distance));
+                }
+            }
+            true
+        }
+    );
+    topmost_candidate(candidates)
+}
 
+// candidates are (entity, z, distance to the hand). the entity only breaks exact ties,
+// so the same pile always gives the same answer
+fn topmost_candidate(candidates: Vec<(Entity, f32, f32)>) -> Option<Entity>{
+    candidates.into_iter()
+        .max_by(|(entity_a, z_a, distance_a), (entity_b, z_b, distance_b)| {
+            z_a.total_cmp(z_b)
+                .then(distance_b.total_cmp(distance_a))
+                .then(entity_b.cmp(entity_a))
+        })
+        .map(|(entity, _, _)| entity)
 }
 
 fn handle_grabbing(
     mut cmds: Commands,
     rapier_context: ReadRapierContext,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    action_state: Res<ActionState>,
+    hand_settings: Res<HandSettings>,
     mut hand_q: Query<(Entity, &mut Hand, &Transform)>,
-    rigidbody_query: Query<(Entity, &Transform, &RigidBody), With<Collider>>,
+    rigidbody_query: GrabbableQuery,
+    mass_q: Query<&ReadMassProperties>,
+    ccd_q: Query<&Ccd>,
+    mut grab_released_msgs: MessageWriter<GrabReleased>,
 ){
     let rapier_context = rapier_context.single().unwrap();
     let Ok((hand_entity, mut hand, hand_transform,)) = hand_q.single_mut() else {return};
 
-    if mouse_buttons.just_pressed(MouseButton::Left) {
-        rapier_context.intersect_shape(
-            hand_transform.translation.truncate(), 
-            0.0, 
-            Collider::ball(5.0).raw.make_mut(), 
-            QueryFilter::default(),
-            | entity| {
-                if hand_entity == entity{return true;}
-
-                if let Ok((rigidbody_entity, rigidbody_transform, rigidbody)) = rigidbody_query.get(entity) {
-                    if matches!(rigidbody, RigidBody::Dynamic){
-                        let grabbed_pos = rigidbody_transform.translation.truncate();
-                        let offset_world = hand_transform.translation.truncate() - grabbed_pos;
-                        
-                        let rotation = rigidbody_transform.rotation;
-                        let offset_local = rotation.inverse() * offset_world.extend(0.0);
-                        
-                        let grab_joint = RevoluteJointBuilder::new()
-                            .local_anchor1(offset_local.truncate())
-                            .local_anchor2(Vec2::ZERO)
-                            .motor_position(0.0, 1.0, 5.0);
-
-                        let joint_entity = cmds.spawn(
-                            ImpulseJoint::new(entity, grab_joint)
-                        ).set_parent_in_place(hand_entity).id();
-
-                        info!("Grabbed entity: {:?}", rigidbody_entity);
-                        
-                        hand.is_grabbing = true;
-                        hand.grab_joint_entity = Some(joint_entity);
-                        hand.grabbed_body = Some(entity);
-
-                        return false;
-                    }
+    if action_state.just_pressed(InputAction::Grab) {
+        let hand_pos = hand_transform.translation.truncate();
+        if let Some(entity) = find_grab_target(&rapier_context, hand_entity, hand_pos, &rigidbody_query) {
+            if let Ok((rigidbody_entity, rigidbody_transform, _)) = rigidbody_query.get(entity) {
+                let grabbed_pos = rigidbody_transform.translation.truncate();
+                let offset_world = hand_pos - grabbed_pos;
+                
+                let rotation = rigidbody_transform.rotation;
+                let offset_local = rotation.inverse() * offset_world.extend(0.0);
+                
+                let mass = mass_q.get(entity).map(|read_mass| read_mass.get().mass).unwrap_or(GRAB_FALLBACK_MASS);
+                let mut grab_joint = GenericJointBuilder::new(JointAxesMask::empty())
+                    .local_anchor1(offset_local.truncate())
+                    .local_anchor2(Vec2::ZERO)
+                    .motor_position(JointAxis::AngX, 0.0, GRAB_ROTATE_STIFFNESS, GRAB_ROTATE_DAMPING)
+                    .build();
+                set_grab_spring(&mut grab_joint, mass, &hand_settings);
+
+                let joint_entity = cmds.spawn(
+                    ImpulseJoint::new(entity, grab_joint)
+                ).set_parent_in_place(hand_entity).id();
+                // fast drags should not pull the body through thin walls
+                let grab_added_ccd = !ccd_q.get(entity).is_ok_and(|ccd| ccd.enabled);
+                if grab_added_ccd {
+                    cmds.entity(entity).insert(Ccd::enabled());
+                }
+                if !mass_q.contains(entity) {
+                    cmds.entity(entity).insert(ReadMassProperties::default());
                 }
-                return false;
+
+                info!("Grabbed entity: {:?}", rigidbody_entity);
+                
+                hand.is_grabbing = true;
+                hand.grab_joint_entity = Some(joint_entity);
+                hand.grabbed_body = Some(entity);
+                hand.target_angle = 0.0;
+                hand.grab_anchor = offset_local.truncate();
+                hand.grab_mass = mass;
+                hand.grab_added_ccd = grab_added_ccd;
             }
-        );
+        }
     }
-    if mouse_buttons.just_released(MouseButton::Left){
-        if hand.is_grabbing {
-            if let Some(joint_entity) = hand.grab_joint_entity {
-            cmds.entity(joint_entity).despawn();
+    // checks the held state instead of the release, which may have happened while paused
+    if hand.is_grabbing && !action_state.pressed(InputAction::Grab){
+        // throw the item with the hands motion instead of letting it drop limply
+        if let Some(grabbed_body) = hand.grabbed_body {
+            if rigidbody_query.contains(grabbed_body) {
+                let throw_velocity = hand.velocity.clamp_length_max(hand_settings.max_throw_speed);
+                cmds.entity(grabbed_body).insert(Velocity::linear(throw_velocity));
+            }
         }
-            hand.is_grabbing = false;
-            hand.grab_joint_entity = None;
-            hand.grabbed_body = None;
+        release_grab(&mut cmds, &mut hand, GrabReleaseReason::Released, &mut grab_released_msgs);
+    }
+}
+
+// the only place the hand lets go of things, so the joint never outlives the grab
+fn release_grab(
+    cmds: &mut Commands,
+    hand: &mut Hand,
+    reason: GrabReleaseReason,
+    grab_released_msgs: &mut MessageWriter<GrabReleased>,
+){
+    if let Some(joint_entity) = hand.grab_joint_entity.take() {
+        cmds.entity(joint_entity).try_despawn();
+    }
+    if let Some(grabbed_body) = hand.grabbed_body.take() {
+        if hand.grab_added_ccd {
+            cmds.entity(grabbed_body).try_remove::<Ccd>();
         }
+        grab_released_msgs.write(GrabReleased { body: grabbed_body, reason });
     }
+    hand.is_grabbing = false;
+    hand.target_angle = 0.0;
+    hand.grab_added_ccd = false;
 }
 
+// held bodies can be despawned by anything, e.g. a machine taking the item in
+fn release_missing_body(
+    mut cmds: Commands,
+    mut hand_q: Query<&mut Hand>,
+    body_q: Query<(), With<RigidBody>>,
+    mut grab_released_msgs: MessageWriter<GrabReleased>,
+){
+    let Ok(mut hand) = hand_q.single_mut() else {return};
+    let Some(grabbed_body) = hand.grabbed_body else {return};
+    if body_q.contains(grabbed_body) {return;}
+
+    info!("held body {:?} is gone", grabbed_body);
+    release_grab(&mut cmds, &mut hand, GrabReleaseReason::BodyRemoved, &mut grab_released_msgs);
+}
+
+fn raise_grabbed_body(
+    hand_q: Query<&Hand>,
+    mut transform_q: Query<(Entity, &mut Transform), (With<RigidBody>, Without<Hand>)>,
+    mut last_raised: Local<Option<Entity>>,
+    mut grab_count: Local<u32>,
+){
+    let Ok(hand) = hand_q.single() else {return};
+    if hand.grabbed_body == *last_raised {return;}
+    *last_raised = hand.grabbed_body;
+
+    let Some(grabbed_body) = hand.grabbed_body else {return};
+    if *grab_count >= GRAB_RAISE_LIMIT {
+        // keeps the stacking order, only the gaps left by despawned bodies go away
+        let mut raised: Vec<(Entity, f32)> = transform_q.iter()
+            .filter(|(_, transform)| transform.translation.z > GRAB_BASE_Z)
+            .map(|(entity, transform)| (entity, transform.translation.z))
+            .collect();
+        raised.sort_by(|(_, z_a), (_, z_b)| z_a.total_cmp(z_b));
+        for (index, (entity, _)) in raised.iter().enumerate(){
+            let Ok((_, mut transform)) = transform_q.get_mut(*entity) else {continue};
+            transform.translation.z = GRAB_BASE_Z + (index + 1) as f32 * GRAB_Z_STEP;
+        }
+        *grab_count = raised.len() as u32;
+    }
+    let Ok((_, mut transform)) = transform_q.get_mut(grabbed_body) else {return};
+    *grab_count += 1;
+    transform.translation.z = GRAB_BASE_Z + *grab_count as f32 * GRAB_Z_STEP;
+}
+
+fn rotate_grabbed_body(
+    time: Res<Time>,
+    action_state: Res<ActionState>,
+    mut hand_q: Query<&mut Hand>,
+    mut joint_q: Query<&mut ImpulseJoint>,
+){
+    let Ok(mut hand) = hand_q.single_mut() else {return};
+    let Some(joint_entity) = hand.grab_joint_entity else {return};
+
+    let mut rotation = action_state.scroll_steps() * GRAB_ROTATE_STEP;
+    if action_state.pressed(InputAction::RotateLeft) {
+        rotation += GRAB_ROTATE_SPEED * time.delta_secs();
+    }
+    if action_state.pressed(InputAction::RotateRight) {
+        rotation -= GRAB_ROTATE_SPEED * time.delta_secs();
+    }
+    if rotation == 0.0 {return;}
+    hand.target_angle += rotation;
+
+    let Ok(mut joint) = joint_q.get_mut(joint_entity) else {return};
+    if let TypedJoint::GenericJoint(grab_joint) = &mut joint.data {
+        grab_joint.set_motor_position(JointAxis::AngX, hand.target_angle, GRAB_ROTATE_STIFFNESS, GRAB_ROTATE_DAMPING);
+    }
+}
+
+// the linear motors act as a force based spring, so mass decides how quickly a body follows
+fn set_grab_spring(grab_joint: &mut GenericJoint, mass: f32, hand_settings: &HandSettings){
+    let mass = mass.max(0.001);
+    let stiffness = hand_settings.grab_stiffness * mass.sqrt();
+    let damping = 2.0 * (stiffness * mass).sqrt() * hand_settings.grab_damping_ratio;
+    for axis in [JointAxis::LinX, JointAxis::LinY] {
+        grab_joint
+            .set_motor_model(axis, MotorModel::ForceBased)
+            .set_motor_position(axis, 0.0, stiffness, damping)
+            .set_motor_max_force(axis, hand_settings.grab_max_force);
+    }
+}
+
+// retunes the grab spring once rapier knows the mass and lets go when the body is pulled too hard
+fn drive_grab_joint(
+    mut cmds: Commands,
+    hand_settings: Res<HandSettings>,
+    mut hand_q: Query<(&mut Hand, &Transform)>,
+    body_q: Query<(&Transform, Option<&ReadMassProperties>), Without<Hand>>,
+    mut joint_q: Query<&mut ImpulseJoint>,
+    mut grab_released_msgs: MessageWriter<GrabReleased>,
+){
+    let Ok((mut hand, hand_transform)) = hand_q.single_mut() else {return};
+    let (Some(joint_entity), Some(grabbed_body)) = (hand.grab_joint_entity, hand.grabbed_body) else {return};
+    let Ok((body_transform, read_mass)) = body_q.get(grabbed_body) else {return};
+
+    let mass = read_mass.map(|read_mass| read_mass.get().mass).filter(|mass| *mass > 0.0).unwrap_or(GRAB_FALLBACK_MASS);
+    // only touch the joint when the mass changed, writing it wakes rapiers joint sync
+    if mass != hand.grab_mass {
+        let Ok(mut joint) = joint_q.get_mut(joint_entity) else {return};
+        let TypedJoint::GenericJoint(grab_joint) = &mut joint.data else {return};
+        set_grab_spring(grab_joint, mass, &hand_settings);
+        hand.grab_mass = mass;
+    }
+
+    let anchor_pos = body_transform.transform_point(hand.grab_anchor.extend(0.0)).truncate();
+    let stretch = anchor_pos.distance(hand_transform.translation.truncate());
+    let pull = hand_settings.grab_stiffness * mass.sqrt() * stretch;
+    if pull > hand_settings.grab_break_force {
+        info!("grip on {:?} broke", grabbed_body);
+        release_grab(&mut cmds, &mut hand, GrabReleaseReason::GripBroken, &mut grab_released_msgs);
+    }
+}
+
+// grabs of items are also reported as item messages, the hammer and other bodies are not
+fn report_item_grabs(
+    hand_q: Query<&Hand>,
+    item_q: Query<&Item>,
+    mut grab_released_msgs: MessageReader<GrabReleased>,
+    mut item_grabbed_msgs: MessageWriter<ItemGrabbed>,
+    mut item_released_msgs: MessageWriter<ItemReleased>,
+    mut held_item: Local<Option<(Entity, String)>>,
+){
+    for grab_released in grab_released_msgs.read(){
+        let Some((item, name)) = held_item.take_if(|(item, _)| *item == grab_released.body) else {continue;};
+        item_released_msgs.write(ItemReleased { item, name, reason: grab_released.reason });
+    }
+
+    let Ok(hand) = hand_q.single() else {return};
+    let Some(grabbed_body) = hand.grabbed_body else {return};
+    if held_item.as_ref().is_some_and(|(item, _)| *item == grabbed_body) {return;}
+    let Ok(item) = item_q.get(grabbed_body) else {return};
+    *held_item = Some((grabbed_body, item.name.clone()));
+    item_grabbed_msgs.write(ItemGrabbed { item: grabbed_body, name: item.name.clone() });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.spawn_empty().id()).collect()
+    }
+
+    #[test]
+    fn highest_body_wins_even_when_further_away() {
+        let spawned = entities(2);
+        let (low, high) = (spawned[0], spawned[1]);
+        assert_eq!(topmost_candidate(vec![(low, 1.0, 0.0), (high, 1.002, 4.0)]), Some(high));
+    }
+
+    #[test]
+    fn closest_body_breaks_height_ties() {
+        let spawned = entities(2);
+        let (far, near) = (spawned[0], spawned[1]);
+        assert_eq!(topmost_candidate(vec![(far, 1.0, 4.0), (near, 1.0, 1.0)]), Some(near));
+    }
+
+    #[test]
+    fn exact_ties_do_not_depend_on_query_order() {
+        let spawned = entities(2);
+        let (first, second) = (spawned[0], spawned[1]);
+        let picked = topmost_candidate(vec![(first, 1.0, 2.0), (second, 1.0, 2.0)]);
+        assert_eq!(picked, topmost_candidate(vec![(second, 1.0, 2.0), (first, 1.0, 2.0)]));
+        assert_eq!(picked, Some(first));
+    }
+
+    #[test]
+    fn nothing_under_the_hand_grabs_nothing() {
+        assert_eq!(topmost_candidate(Vec::new()), None);
+    }
+}
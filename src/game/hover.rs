@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::game::{hand::{GrabbableQuery, Hand, HandUpdateSet, find_grab_target}, interactable::{DropTargetQuery, find_click_target, find_drop_target}, item::Item};
+
+pub struct HoverPlugin;
+impl Plugin for HoverPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoverState>();
+        app.add_systems(Startup, setup_hover_outlines);
+        app.add_systems(Update, update_hover.after(HandUpdateSet));
+        app.add_systems(PostUpdate, (
+            update_hover_outlines,
+            update_hand_cursor,
+        ).after(PhysicsSet::Writeback));
+    }
+}
+
+const HOVER_OUTLINE_PADDING: f32 = 6.0;
+const GRAB_OUTLINE_COLOR: Color = Color::linear_rgb(1.0, 1.0, 1.0);
+const DROP_OUTLINE_COLOR: Color = Color::linear_rgb(0.2, 1.0, 0.2);
+const HAND_IDLE_COLOR: Color = Color::linear_rgb(0.1, 0.1, 0.1);
+const HAND_HOVER_COLOR: Color = Color::linear_rgb(0.9, 0.9, 0.9);
+const HAND_GRABBING_COLOR: Color = Color::linear_rgb(0.9, 0.6, 0.1);
+
+#[derive(Resource, Default, Debug)]
+pub struct HoverState{
+    // what a click would grab or use right now
+    pub hovered: Option<Entity>,
+    // what the held item would be accepted by if it was released now
+    pub drop_target: Option<Entity>,
+}
+
+#[derive(Component)]
+enum HoverOutline{
+    Hovered,
+    DropTarget,
+}
+
+fn setup_hover_outlines(
+    mut cmds: Commands,
+){
+    for (outline, color) in [(HoverOutline::Hovered, GRAB_OUTLINE_COLOR), (HoverOutline::DropTarget, DROP_OUTLINE_COLOR)]{
+        cmds.spawn((
+            outline,
+            Sprite::from_color(color, Vec2::ONE),
+            Transform::default(),
+            Visibility::Hidden,
+        ));
+    }
+}
+
+fn update_hover(
+    rapier_context: ReadRapierContext,
+    mut hover_state: ResMut<HoverState>,
+    hand_q: Query<(Entity, &Hand, &Transform)>,
+    rigidbody_query: GrabbableQuery,
+    drop_target_q: DropTargetQuery,
+    item_q: Query<&Item>,
+){
+    let Ok(rapier_context) = rapier_context.single() else {return};
+    let Ok((hand_entity, hand, hand_transform)) = hand_q.single() else {return};
+    let hand_pos = hand_transform.translation.truncate();
+
+    hover_state.hovered = None;
+    hover_state.drop_target = None;
+
+    if let Some(held_entity) = hand.grabbed_body {
+        let Ok(item) = item_q.get(held_entity) else {return};
+        hover_state.drop_target = find_drop_target(&rapier_context, held_entity, item, &drop_target_q)
+            .filter(|target_entity| drop_target_q.get(*target_entity).is_ok_and(|(_, accepts_items)| accepts_items.accepts(item)));
+        return;
+    }
+
+    hover_state.hovered = find_grab_target(&rapier_context, hand_entity, hand_pos, &rigidbody_query)
+        .or_else(|| find_click_target(&rapier_context, hand_pos, &drop_target_q));
+}
+
+fn update_hover_outlines(
+    hover_state: Res<HoverState>,
+    target_q: Query<(&Transform, &Sprite), Without<HoverOutline>>,
+    mut outline_q: Query<(&HoverOutline, &mut Transform, &mut Sprite, &mut Visibility)>,
+){
+    for (outline, mut outline_transform, mut outline_sprite, mut visibility) in outline_q.iter_mut(){
+        let target = match outline {
+            HoverOutline::Hovered => hover_state.hovered,
+            HoverOutline::DropTarget => hover_state.drop_target,
+        };
+        let Some((target_transform, target_size)) = target
+            .and_then(|target| target_q.get(target).ok())
+            .and_then(|(target_transform, target_sprite)| Some((target_transform, target_sprite.custom_size?)))
+        else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        // drawn slightly bigger and just behind the target so only the border shows
+        outline_transform.translation = target_transform.translation -Vec3::Z * 0.05;
+        outline_transform.rotation = target_transform.rotation;
+        outline_sprite.custom_size = Some(target_size +Vec2::splat(HOVER_OUTLINE_PADDING));
+        *visibility = Visibility::Visible;
+    }
+}
+
+fn update_hand_cursor(
+    hover_state: Res<HoverState>,
+    mut hand_q: Query<(&Hand, &mut Sprite)>,
+){
+    for (hand, mut sprite) in hand_q.iter_mut(){
+        sprite.color = if hand.is_grabbing {
+            HAND_GRABBING_COLOR
+        } else if hover_state.hovered.is_some() {
+            HAND_HOVER_COLOR
+        } else {
+            HAND_IDLE_COLOR
+        };
+    }
+}
@@ -0,0 +1,356 @@
+use bevy::{input::{InputSystems, mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit}}, platform::collections::{HashMap, HashSet}, prelude::*, reflect::{DynamicEnum, DynamicVariant, Enum, FromReflect, Typed}};
+use serde::{Deserialize, Serialize};
+
+pub struct InputActionPlugin;
+impl Plugin for InputActionPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputBindings>();
+        app.init_resource::<ActionState>();
+        app.init_resource::<PendingRebind>();
+        app.init_resource::<LastInputDevice>();
+        app.init_resource::<VirtualActions>();
+        app.configure_sets(PreUpdate, ActionUpdateSet.after(InputSystems));
+        app.configure_sets(Update, WorldInputSet.run_if(not(pointer_over_ui)));
+        app.add_systems(PreUpdate, (
+            detect_input_device,
+            capture_rebind,
+            update_action_state,
+        ).chain().in_set(ActionUpdateSet));
+    }
+}
+
+pub const GAMEPAD_STICK_DEADZONE: f32 = 0.15;
+const SCROLL_PIXELS_PER_LINE: f32 = 100.0;
+
+// input sources that are not plain buttons (e.g. touch) fill VirtualActions before this
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionUpdateSet;
+
+// systems that act on the world under the hand, skipped while the hand is over or clicking UI
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WorldInputSet;
+
+fn pointer_over_ui(
+    interaction_q: Query<&Interaction>,
+) -> bool{
+    interaction_q.iter().any(|interaction| *interaction != Interaction::None)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InputDevice{
+    #[default]
+    Mouse,
+    Gamepad,
+    Touch,
+}
+
+// the hand follows whichever device was used last
+#[derive(Resource, Debug, Default, PartialEq, Eq)]
+pub struct LastInputDevice(pub InputDevice);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputAction{
+    Grab,
+    Interact,
+    RoomLeft,
+    RoomRight,
+    RoomUp,
+    RoomDown,
+    Pause,
+    Slap,
+    RotateLeft,
+    RotateRight,
+    ToggleLabels,
+}
+
+impl InputAction{
+    pub const ALL: [InputAction; 11] = [
+        InputAction::Grab,
+        InputAction::Interact,
+        InputAction::RoomLeft,
+        InputAction::RoomRight,
+        InputAction::RoomUp,
+        InputAction::RoomDown,
+        InputAction::Pause,
+        InputAction::Slap,
+        InputAction::RotateLeft,
+        InputAction::RotateRight,
+        InputAction::ToggleLabels,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputBinding{
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+// bevy's input types are not serializable without the `serialize` feature,
+// so bindings are saved by their variant name instead
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SavedBinding{
+    Key(String),
+    Mouse(String),
+    Gamepad(String),
+}
+
+impl InputBinding{
+    // e.g. "KeyQ" or "LeftTrigger", shown in the settings menu
+    pub fn name(&self) -> &str{
+        match self {
+            InputBinding::Key(key) => key.variant_name(),
+            InputBinding::Mouse(button) => button.variant_name(),
+            InputBinding::Gamepad(button) => button.variant_name(),
+        }
+    }
+
+    pub fn to_saved(&self) -> SavedBinding{
+        match self {
+            InputBinding::Key(key) => SavedBinding::Key(key.variant_name().to_string()),
+            InputBinding::Mouse(button) => SavedBinding::Mouse(button.variant_name().to_string()),
+            InputBinding::Gamepad(button) => SavedBinding::Gamepad(button.variant_name().to_string()),
+        }
+    }
+
+    pub fn from_saved(saved: &SavedBinding) -> Option<Self>{
+        match saved {
+            SavedBinding::Key(name) => unit_variant_from_name(name).map(InputBinding::Key),
+            SavedBinding::Mouse(name) => unit_variant_from_name(name).map(InputBinding::Mouse),
+            SavedBinding::Gamepad(name) => unit_variant_from_name(name).map(InputBinding::Gamepad),
+        }
+    }
+}
+
+fn unit_variant_from_name<T: FromReflect + Typed>(name: &str) -> Option<T>{
+    let dynamic_enum = DynamicEnum::new(name.to_string(), DynamicVariant::Unit);
+    T::from_reflect(&dynamic_enum)
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct InputBindings(pub HashMap<InputAction, Vec<InputBinding>>);
+
+impl Default for InputBindings{
+    fn default() -> Self {
+        use InputBinding::*;
+        let mut bindings = HashMap::new();
+        bindings.insert(InputAction::Grab, vec![Mouse(MouseButton::Left), Gamepad(GamepadButton::South), Gamepad(GamepadButton::RightTrigger2)]);
+        bindings.insert(InputAction::Interact, vec![Mouse(MouseButton::Right), Gamepad(GamepadButton::West)]);
+        bindings.insert(InputAction::RoomLeft, vec![Key(KeyCode::KeyA), Key(KeyCode::ArrowLeft), Gamepad(GamepadButton::DPadLeft)]);
+        bindings.insert(InputAction::RoomRight, vec![Key(KeyCode::KeyD), Key(KeyCode::ArrowRight), Gamepad(GamepadButton::DPadRight)]);
+        bindings.insert(InputAction::RoomUp, vec![Key(KeyCode::KeyW), Key(KeyCode::ArrowUp), Gamepad(GamepadButton::DPadUp)]);
+        bindings.insert(InputAction::RoomDown, vec![Key(KeyCode::KeyS), Key(KeyCode::ArrowDown), Gamepad(GamepadButton::DPadDown)]);
+        bindings.insert(InputAction::Pause, vec![Key(KeyCode::Escape), Gamepad(GamepadButton::Start)]);
+        bindings.insert(InputAction::Slap, vec![Key(KeyCode::Space), Gamepad(GamepadButton::East)]);
+        // the bumpers, so a held item can be turned while the grab trigger stays down
+        bindings.insert(InputAction::RotateLeft, vec![Key(KeyCode::KeyQ), Gamepad(GamepadButton::LeftTrigger)]);
+        bindings.insert(InputAction::RotateRight, vec![Key(KeyCode::KeyE), Gamepad(GamepadButton::RightTrigger)]);
+        bindings.insert(InputAction::ToggleLabels, vec![Key(KeyCode::KeyL), Gamepad(GamepadButton::Select)]);
+        InputBindings(bindings)
+    }
+}
+
+impl InputBindings{
+    pub fn get(&self, action: InputAction) -> &[InputBinding]{
+        self.0.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // replaces the bindings of the same device kind, so rebinding a key keeps the gamepad binding
+    pub fn rebind(&mut self, action: InputAction, binding: InputBinding){
+        let bindings = self.0.entry(action).or_default();
+        bindings.retain(|bound| std::mem::discriminant(bound) != std::mem::discriminant(&binding));
+        bindings.push(binding);
+    }
+
+    pub fn to_saved(&self) -> Vec<(InputAction, Vec<SavedBinding>)>{
+        InputAction::ALL.iter()
+            .map(|action| (*action, self.get(*action).iter().map(InputBinding::to_saved).collect()))
+            .collect()
+    }
+
+    // actions missing from the saved list keep their default bindings
+    pub fn apply_saved(&mut self, saved: &[(InputAction, Vec<SavedBinding>)]){
+        for (action, saved_bindings) in saved.iter(){
+            let bindings = saved_bindings.iter()
+                .filter_map(|saved_binding| {
+                    let binding = InputBinding::from_saved(saved_binding);
+                    if binding.is_none() {
+                        warn!("unknown input binding {:?} for {:?}", saved_binding, action);
+                    }
+                    binding
+                })
+                .collect();
+            self.0.insert(*action, bindings);
+        }
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct ActionState{
+    pressed: HashSet<InputAction>,
+    just_pressed: HashSet<InputAction>,
+    just_released: HashSet<InputAction>,
+    // mouse wheel notches this frame, positive is away from the player
+    scroll_steps: f32,
+}
+
+impl ActionState{
+    pub fn pressed(&self, action: InputAction) -> bool{
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool{
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: InputAction) -> bool{
+        self.just_released.contains(&action)
+    }
+
+    pub fn scroll_steps(&self) -> f32{
+        self.scroll_steps
+    }
+
+    fn set(&mut self, action: InputAction, down: bool){
+        let was_down = self.pressed.contains(&action);
+        if down && !was_down {
+            self.pressed.insert(action);
+            self.just_pressed.insert(action);
+        }
+        if !down && was_down {
+            self.pressed.remove(&action);
+            self.just_released.insert(action);
+        }
+    }
+}
+
+fn detect_input_device(
+    mut last_input_device: ResMut<LastInputDevice>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    gamepad_q: Query<&Gamepad>,
+){
+    let gamepad_used = gamepad_q.iter().any(|gamepad| {
+        gamepad.get_just_pressed().next().is_some() || gamepad.left_stick().length() > GAMEPAD_STICK_DEADZONE
+    });
+    let mouse_used = mouse_motion.delta != Vec2::ZERO || mouse_buttons.get_just_pressed().next().is_some();
+
+    if touches.any_just_pressed() {
+        last_input_device.set_if_neq(LastInputDevice(InputDevice::Touch));
+    } else if gamepad_used {
+        last_input_device.set_if_neq(LastInputDevice(InputDevice::Gamepad));
+    } else if mouse_used {
+        last_input_device.set_if_neq(LastInputDevice(InputDevice::Mouse));
+    }
+}
+
+// actions held down by sources other than the bindings, refilled every frame
+#[derive(Resource, Default, Debug)]
+pub struct VirtualActions(pub HashSet<InputAction>);
+
+// when set, the next pressed button replaces the binding of this action, escape cancels.
+// set from the settings menu
+#[derive(Resource, Default, Debug)]
+pub struct PendingRebind(pub Option<InputAction>);
+
+fn capture_rebind(
+    mut pending_rebind: ResMut<PendingRebind>,
+    mut input_bindings: ResMut<InputBindings>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut mouse_buttons: ResMut<ButtonInput<MouseButton>>,
+    gamepad_q: Query<&Gamepad>,
+){
+    let Some(action) = pending_rebind.0 else {return};
+    // the press is used up here, so it does not also trigger whatever it is bound to
+    if keyboard.just_pressed(KeyCode::Escape) {
+        keyboard.reset(KeyCode::Escape);
+        pending_rebind.0 = None;
+        return;
+    }
+
+    let binding = keyboard.get_just_pressed().next().map(|key| InputBinding::Key(*key))
+        .or_else(|| mouse_buttons.get_just_pressed().next().map(|button| InputBinding::Mouse(*button)))
+        .or_else(|| gamepad_q.iter()
+            .find_map(|gamepad| gamepad.get_just_pressed().next())
+            .map(|button| InputBinding::Gamepad(*button)));
+    let Some(binding) = binding else {return};
+
+    match binding {
+        InputBinding::Key(key) => keyboard.reset(key),
+        InputBinding::Mouse(button) => mouse_buttons.reset(button),
+        InputBinding::Gamepad(_) => {}
+    }
+    info!("rebound {:?} to {:?}", action, binding);
+    input_bindings.rebind(action, binding);
+    pending_rebind.0 = None;
+}
+
+fn update_action_state(
+    mut action_state: ResMut<ActionState>,
+    pending_rebind: Res<PendingRebind>,
+    input_bindings: Res<InputBindings>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    gamepad_q: Query<&Gamepad>,
+    virtual_actions: Res<VirtualActions>,
+    mouse_scroll: Res<AccumulatedMouseScroll>,
+){
+    action_state.just_pressed.clear();
+    action_state.just_released.clear();
+    action_state.scroll_steps = match mouse_scroll.unit {
+        MouseScrollUnit::Line => mouse_scroll.delta.y,
+        MouseScrollUnit::Pixel => mouse_scroll.delta.y / SCROLL_PIXELS_PER_LINE,
+    };
+
+    for action in InputAction::ALL{
+        // the button that is being bound should not also trigger its old action
+        let down = pending_rebind.0.is_none() && input_bindings.get(action).iter().any(|binding| match binding {
+            InputBinding::Key(key) => keyboard.pressed(*key),
+            InputBinding::Mouse(button) => mouse_buttons.pressed(*button),
+            InputBinding::Gamepad(button) => gamepad_q.iter().any(|gamepad| gamepad.pressed(*button)),
+        }) || virtual_actions.0.contains(&action);
+        action_state.set(action, down);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_default_binding_survives_saving() {
+        for (action, bindings) in InputBindings::default().0.iter(){
+            for binding in bindings{
+                assert_eq!(InputBinding::from_saved(&binding.to_saved()), Some(*binding), "{:?}", action);
+            }
+        }
+    }
+
+    #[test]
+    fn saved_bindings_round_trip_through_ron() {
+        let mut input_bindings = InputBindings::default();
+        input_bindings.rebind(InputAction::Grab, InputBinding::Key(KeyCode::KeyG));
+        input_bindings.0.insert(InputAction::Slap, vec![InputBinding::Gamepad(GamepadButton::East)]);
+
+        let contents = ron::ser::to_string(&input_bindings.to_saved()).unwrap();
+        let saved: Vec<(InputAction, Vec<SavedBinding>)> = ron::de::from_str(&contents).unwrap();
+        let mut loaded = InputBindings(HashMap::new());
+        loaded.apply_saved(&saved);
+
+        for action in InputAction::ALL{
+            assert_eq!(loaded.get(action), input_bindings.get(action), "{:?}", action);
+        }
+    }
+
+    #[test]
+    fn unknown_saved_bindings_are_dropped_and_missing_actions_keep_defaults() {
+        let mut input_bindings = InputBindings::default();
+        input_bindings.apply_saved(&[(InputAction::Pause, vec![
+            SavedBinding::Key("KeyP".to_string()),
+            SavedBinding::Key("KeyNotOnAnyKeyboard".to_string()),
+        ])]);
+        assert_eq!(input_bindings.get(InputAction::Pause), &[InputBinding::Key(KeyCode::KeyP)]);
+        assert_eq!(input_bindings.get(InputAction::Grab), InputBindings::default().get(InputAction::Grab));
+    }
+}
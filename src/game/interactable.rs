@@ -1,95 +1,141 @@
 use bevy::prelude::*;
-use bevy_rapier2d::{plugin::{RapierContext, ReadRapierContext}, prelude::Collider};
+use bevy_rapier2d::{plugin::{RapierContext, ReadRapierContext}, prelude::{Collider, QueryFilter}};
 
-use crate::game::{hand::{Hand, HandUpdateSet}, interactable, item::{self, Item}, machine::{self, Machine}, npc::{NPC, Reward}};
+use crate::game::{hand::{GrabbableQuery, HAND_RADIUS, Hand, HandUpdateSet, find_grab_target}, input::{ActionState, InputAction, WorldInputSet}, item::Item, states::GameplaySet};
 
 pub struct InteractablePlugin;
 impl Plugin for InteractablePlugin{
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, handle_interactions.before(HandUpdateSet));
+        app.add_systems(Update, handle_interactions.before(HandUpdateSet).in_set(GameplaySet).in_set(WorldInputSet));
     }
 }
 
-#[derive(Component)]
-pub struct Interactable{
-    pub interaction_type: InteractionType
+// anything the hand can click or drop items onto. what happens is up to the module that
+// spawned it, by observing `Clicked` and `ItemDropped`
+#[derive(Component, Default)]
+#[require(AcceptsItems)]
+pub struct Interactable;
+
+// which items an interactable would take right now, kept up to date by its owning module
+// and used to pick drop targets and for hover feedback
+#[derive(Component, Default, Debug, Clone, PartialEq, Eq)]
+pub enum AcceptsItems{
+    #[default]
+    Nothing,
+    Any,
+    Only(String),
 }
 
-pub enum InteractionType {
-    None,
-    Item,
-    Talk,
-    Button{ action: Box<dyn Fn(&mut Commands) + Send + Sync>},
-    Quest { wants: String, reward: Reward },
-    Machine,
+impl AcceptsItems{
+    pub fn accepts(&self, item: &Item) -> bool{
+        match self {
+            AcceptsItems::Nothing => false,
+            AcceptsItems::Any => true,
+            AcceptsItems::Only(name) => *name == item.name,
+        }
+    }
 }
 
-pub fn spawn_any_button<M: Message + Clone>(
-    cmds: &mut Commands, 
-    pos: Vec2, 
-    message: M
-) {
-    cmds.spawn((
-        Interactable { 
-            interaction_type: InteractionType::Button {
-                action: Box::new(move |cmds| {
-                    // This sends the specific message type M
-                    cmds.write_message(message.clone());
-                })
-            }
-        },
-        Transform::from_xyz(pos.x, pos.y, 0.0),
-        Collider::cuboid(8.0, 8.0),
-    ));
+// the hand let go of `item` on top of `target`. the item is still there, handlers
+// despawn it if they take it
+#[derive(EntityEvent, Debug, Clone)]
+pub struct ItemDropped{
+    #[event_target]
+    pub target: Entity,
+    pub item: Entity,
 }
 
+// `target` was clicked with nothing to pick up under the hand
+#[derive(EntityEvent, Debug, Clone)]
+pub struct Clicked{
+    #[event_target]
+    pub target: Entity,
+    pub source: ClickSource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickSource{
+    Interact,
+    // a grab that had nothing to pick up, handlers that do something destructive should ignore these
+    Grab,
+}
+
+pub type DropTargetQuery<'w, 's> = Query<'w, 's, (Entity, &'static AcceptsItems), With<Interactable>>;
+
+// the interactable `held_entity` would be dropped onto, shared with the hover feedback.
+// prefers one that accepts the item, otherwise any it overlaps so that one can still react
+pub fn find_drop_target(
+    rapier_context: &RapierContext,
+    held_entity: Entity,
+    item: &Item,
+    drop_target_q: &DropTargetQuery,
+) -> Option<Entity>{
+    let overlapping: Vec<(Entity, &AcceptsItems)> = drop_target_q.iter()
+        .filter(|(target_entity, _)| *target_entity != held_entity)
+        .filter(|(target_entity, _)| rapier_context.intersection_pair(held_entity, *target_entity) == Some(true))
+        .collect();
+    overlapping.iter()
+        .find(|(_, accepts_items)| accepts_items.accepts(item))
+        .or(overlapping.first())
+        .map(|(target_entity, _)| *target_entity)
+}
+
+// the interactable a click at `hand_pos` would go to, shared with the hover feedback
+pub fn find_click_target(
+    rapier_context: &RapierContext,
+    hand_pos: Vec2,
+    drop_target_q: &DropTargetQuery,
+) -> Option<Entity>{
+    let mut clicked_target = None;
+    rapier_context.intersect_shape(
+        hand_pos,
+        0.0,
+        Collider::ball(HAND_RADIUS).raw.make_mut(),
+        QueryFilter::default(),
+        |entity| {
+            if drop_target_q.contains(entity) {
+                clicked_target = Some(entity);
+                return false;
+            }
+            true
+        }
+    );
+    clicked_target
+}
 
 pub fn handle_interactions(
     mut cmds: Commands,
     rapier_context: ReadRapierContext,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
-    mut hand_q: Query<(Entity, &mut Hand)>,
-    interactable_q: Query<(Entity, &Interactable, &Transform)>,
-    mut machine_q: Query<(&mut Machine)>,
+    action_state: Res<ActionState>,
+    hand_q: Query<(Entity, &Hand, &Transform)>,
+    rigidbody_query: GrabbableQuery,
+    drop_target_q: DropTargetQuery,
     item_q: Query<&Item>,
 ) {
-    let Ok((hand_entity, mut hand)) = hand_q.single_mut() else { return };
+    let Ok((hand_entity, hand, hand_transform)) = hand_q.single() else { return };
     let rapier_context = rapier_context.single().unwrap();
+    let hand_pos = hand_transform.translation.truncate();
 
-    if mouse_buttons.just_released(MouseButton::Left) {
-        if let Some(held_entity) = hand.grabbed_body {
-            println!("Item {:?} wurde losgelassen", held_entity);
-            for (target_entity, interactable, transform) in interactable_q.iter() {
-                if target_entity == held_entity {continue;}
-                let collision = rapier_context.intersection_pair(held_entity, target_entity) == Some(true);
-                if collision {
-                    println!("Kollision mit Interactable erkannt!");
-                    match &interactable.interaction_type {
-                        // InteractionType::Quest { wants, reward } => {
-                        //     if let Ok(item) = item_q.get(held_entity) {
-                        //         if item.name == *wants {
-                        //             info!("Quest Complete! Reward: {:?}", reward);
-                        //             cmds.entity(held_entity).despawn();
-                        //             hand.grabbed_body = None; // Clear hand
-                        //         }
-                        //     }
-                        // }
-                        InteractionType::Machine => {
-                            println!("interaction type: Machine");
-                            if let Ok(item) = item_q.get(held_entity) {
-                                if let Ok(mut machine) = machine_q.get_mut(target_entity){
-                                    if machine.items.len() <= machine.capacity{
-                                        machine.items.push(item.name.clone());
-                                        cmds.entity(held_entity).despawn();
-                                        println!("Item in Maschine gelegt: {}", item.name);
-                                    }
-                                }
-                            }
-                        }
-                        _ => {println!("interaction type: unknown")}
-                    }
-                }
-            }
+    let click_source = if action_state.just_pressed(InputAction::Interact) {
+        Some(ClickSource::Interact)
+    } else if action_state.just_pressed(InputAction::Grab)
+        && hand.grabbed_body.is_none()
+        && find_grab_target(&rapier_context, hand_entity, hand_pos, &rigidbody_query).is_none() {
+        Some(ClickSource::Grab)
+    } else {
+        None
+    };
+    if let Some(source) = click_source {
+        if let Some(target) = find_click_target(&rapier_context, hand_pos, &drop_target_q) {
+            cmds.trigger(Clicked { target, source });
+        }
+    }
+
+    if action_state.just_released(InputAction::Grab) {
+        let Some(held_entity) = hand.grabbed_body else {return};
+        let Ok(item) = item_q.get(held_entity) else {return};
+        if let Some(target) = find_drop_target(&rapier_context, held_entity, item, &drop_target_q) {
+            cmds.trigger(ItemDropped { target, item: held_entity });
         }
     }
 }
@@ -1,17 +1,25 @@
-use bevy::{platform::collections::HashMap, prelude::*};
+use bevy::{asset::{AssetLoader, LoadContext, io::Reader}, platform::collections::HashMap, prelude::*};
 use bevy_rapier2d::prelude::*;
-
-use crate::game::interactable::{Interactable, InteractionType};
+use serde::Deserialize;
+use thiserror::Error;
 
 pub struct ItemPlugin;
 impl Plugin for ItemPlugin {
     fn build(&self, app: &mut App) {
+        app.init_asset::<ItemCatalogAsset>();
+        app.register_asset_loader(ItemCatalogLoader);
+        app.init_resource::<ItemCatalog>();
+        app.add_message::<ItemSpawned>();
         app.configure_sets(Startup, 
         ItemSetupSet);
         app.add_systems(Startup, setup_item_catalog.in_set(ItemSetupSet));
+        app.add_systems(Update, update_item_catalog);
     }
 }
 
+// run with `--features dev` to hot reload this file
+const ITEM_CATALOG_PATH: &str = "data/kitchen.items.ron";
+
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ItemSetupSet;
 
@@ -20,56 +28,201 @@ pub struct Item {
     pub name: String,
 }
 
+#[derive(Message, Debug, Clone)]
+pub struct ItemSpawned{
+    pub item: Entity,
+    pub name: String,
+    pub pos: Vec2,
+}
+
 pub struct ItemData {
+    pub display_name: String,
+    pub description: String,
     pub sprite: Sprite,
-    pub collider: Collider
+    pub collider: Collider,
+    pub mass: f32,
+    pub tags: Vec<String>,
 }
 impl ItemData{
-    pub fn new(sprite: Sprite, collider: Collider) -> Self{
-        ItemData {sprite, collider}
-    }
-    pub fn from_img_asset(img: Handle<Image>) -> Self{
+    fn from_definition(definition: &ItemDefinition, asset_server: &AssetServer) -> Self{
+        let size = vec2(definition.size.0, definition.size.1);
+        let sprite = match &definition.sprite {
+            ItemSprite::Color(r, g, b) => Sprite::from_color(Color::linear_rgb(*r, *g, *b), size),
+            ItemSprite::Image(path) => Sprite {
+                image: asset_server.load(path),
+                custom_size: Some(size),
+                ..default()
+            },
+        };
+        let collider = match definition.shape {
+            ItemShape::Cuboid => Collider::cuboid(size.x/2.0, size.y/2.0),
+            ItemShape::Ball => Collider::ball(size.x/2.0),
+            ItemShape::Capsule => Collider::capsule_y((size.y -size.x).max(0.0)/2.0, size.x/2.0),
+        };
         ItemData {
-            sprite: Sprite::from_image(img),
-            collider: Collider::cuboid(16.0, 16.0),
+            display_name: definition.display_name.clone(),
+            description: definition.description.clone(),
+            sprite,
+            collider,
+            mass: definition.mass,
+            tags: definition.tags.clone(),
         }
     }
+
+    pub fn has_tag(&self, tag: &str) -> bool{
+        self.tags.iter().any(|item_tag| item_tag == tag)
+    }
 }
 #[derive(Resource, Default)]
 pub struct ItemCatalog(pub HashMap<String, ItemData>);
 
+#[derive(Resource)]
+pub struct ItemCatalogHandle(pub Handle<ItemCatalogAsset>);
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct ItemCatalogAsset {
+    pub items: Vec<ItemDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ItemDefinition {
+    pub name: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub description: String,
+    pub sprite: ItemSprite,
+    #[serde(default)]
+    pub shape: ItemShape,
+    pub size: (f32, f32),
+    #[serde(default = "default_item_mass")]
+    pub mass: f32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_item_mass() -> f32 {1.0}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum ItemSprite {
+    Color(f32, f32, f32),
+    Image(String),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub enum ItemShape {
+    #[default]
+    Cuboid,
+    Ball,
+    Capsule,
+}
+
+#[derive(Debug, Error)]
+pub enum ItemCatalogLoadError {
+    #[error("could not read item catalog: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse item catalog: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid item \"{name}\": {reason}")]
+    InvalidItem { name: String, reason: String },
+}
+
+impl ItemCatalogAsset {
+    fn validate(&self) -> Result<(), ItemCatalogLoadError> {
+        let mut names = Vec::<&str>::new();
+        for item in self.items.iter(){
+            let invalid = |reason: &str| ItemCatalogLoadError::InvalidItem {
+                name: item.name.clone(),
+                reason: reason.to_string(),
+            };
+
+            if item.name.trim().is_empty() {
+                return Err(invalid("name must not be empty"));
+            }
+            if names.contains(&item.name.as_str()) {
+                return Err(invalid("defined more than once"));
+            }
+            if item.size.0 <= 0.0 || item.size.1 <= 0.0 {
+                return Err(invalid("size must be positive"));
+            }
+            if item.mass <= 0.0 {
+                return Err(invalid("mass must be positive"));
+            }
+            if let ItemSprite::Image(path) = &item.sprite {
+                if path.trim().is_empty() {
+                    return Err(invalid("sprite image path must not be empty"));
+                }
+            }
+            names.push(&item.name);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct ItemCatalogLoader;
+
+impl AssetLoader for ItemCatalogLoader {
+    type Asset = ItemCatalogAsset;
+    type Settings = ();
+    type Error = ItemCatalogLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let item_catalog = ron::de::from_bytes::<ItemCatalogAsset>(&bytes)?;
+        item_catalog.validate()?;
+        Ok(item_catalog)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items.ron"]
+    }
+}
+
 
 fn setup_item_catalog(
     mut cmds: Commands,
     asset_server: Res<AssetServer>
 ){
-    let mut items = HashMap::<String, ItemData>::new();
-    items.insert("trash".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(0.0, 0.0, 0.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    items.insert("red".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(1.0, 0.0, 0.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    items.insert("green".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(0.0, 1.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    items.insert("blue".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(0.0, 0.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    items.insert("yellow".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(1.0, 1.0, 0.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    items.insert("violet".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(1.0, 0.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    items.insert("turkeu".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(0.0, 1.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    items.insert("white".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(1.0, 1.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-
-    cmds.insert_resource(ItemCatalog(items));
+    cmds.insert_resource(ItemCatalogHandle(asset_server.load(ITEM_CATALOG_PATH)));
+}
+
+fn update_item_catalog(
+    mut asset_events: MessageReader<AssetEvent<ItemCatalogAsset>>,
+    item_catalog_assets: Res<Assets<ItemCatalogAsset>>,
+    asset_server: Res<AssetServer>,
+    mut item_catalog: ResMut<ItemCatalog>,
+){
+    for asset_event in asset_events.read(){
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = asset_event else {continue;};
+        let Some(item_catalog_asset) = item_catalog_assets.get(*id) else {continue;};
+
+        item_catalog.0 = item_catalog_asset.items.iter()
+            .map(|definition| (definition.name.clone(), ItemData::from_definition(definition, &asset_server)))
+            .collect();
+        info!("item catalog loaded with {} items", item_catalog.0.len());
+    }
 }
 
 
-pub fn spawn_item(cmds: &mut Commands, item_catalog: &Res<ItemCatalog>, pos: Vec2, name: String) {
+pub fn spawn_item(cmds: &mut Commands, item_catalog: &Res<ItemCatalog>, pos: Vec2, name: String) -> Option<Entity> {
     if let Some(item_data) = item_catalog.0.get(&name){
-        cmds.spawn((
-            Item { name },
-            // Interactable { interaction_type: InteractionType::Item },
+        let item = cmds.spawn((
+            Item { name: name.clone() },
             item_data.sprite.clone(),
             Transform::from_xyz(pos.x, pos.y, 1.0),
             RigidBody::Dynamic,
             item_data.collider.clone(),
-        ));
+            ColliderMassProperties::Mass(item_data.mass),
+        )).id();
+        cmds.write_message(ItemSpawned { item, name, pos });
+        return Some(item);
     }
+    warn!("tried to spawn unknown item \"{}\"", name);
+    None
 }
-
-
-
-
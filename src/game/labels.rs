@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::PhysicsSet;
+
+use crate::game::{hand::Hand, hover::HoverState, input::{ActionState, InputAction}, item::{Item, ItemCatalog}};
+
+pub struct ItemLabelPlugin;
+impl Plugin for ItemLabelPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ItemLabelSettings>();
+        app.add_systems(Startup, setup_item_label);
+        app.add_systems(Update, toggle_item_labels);
+        app.add_systems(PostUpdate, update_item_label.after(PhysicsSet::Writeback));
+    }
+}
+
+// drawn just in front of the item it belongs to
+const LABEL_Z_OFFSET: f32 = 1.0;
+// gap between the top of the item and the name
+const LABEL_MARGIN: f32 = 24.0;
+const LABEL_NAME_SIZE: f32 = 18.0;
+const LABEL_DESCRIPTION_SIZE: f32 = 12.0;
+const LABEL_DESCRIPTION_OFFSET: f32 = -18.0;
+const LABEL_NAME_COLOR: Color = Color::WHITE;
+const LABEL_DESCRIPTION_COLOR: Color = Color::srgb(0.8, 0.8, 0.8);
+// higher follows tumbling items more tightly
+const LABEL_FOLLOW_SPEED: f32 = 15.0;
+
+// saved with the other settings
+#[derive(Resource, Debug)]
+pub struct ItemLabelSettings{
+    pub enabled: bool,
+}
+
+impl Default for ItemLabelSettings {
+    fn default() -> Self {
+        ItemLabelSettings { enabled: true }
+    }
+}
+
+// name of the held or hovered item, the description is a child below it
+#[derive(Component)]
+struct ItemLabel{
+    item: Option<Entity>,
+}
+
+#[derive(Component)]
+struct ItemLabelDescription;
+
+fn setup_item_label(
+    mut cmds: Commands,
+){
+    cmds.spawn((
+        ItemLabel { item: None },
+        Text2d::default(),
+        TextFont { font_size: LABEL_NAME_SIZE, ..default() },
+        TextColor(LABEL_NAME_COLOR),
+        Transform::from_xyz(0.0, 0.0, LABEL_Z_OFFSET),
+        Visibility::Hidden,
+    )).with_child((
+        ItemLabelDescription,
+        Text2d::default(),
+        TextFont { font_size: LABEL_DESCRIPTION_SIZE, ..default() },
+        TextColor(LABEL_DESCRIPTION_COLOR),
+        Transform::from_xyz(0.0, LABEL_DESCRIPTION_OFFSET, 0.0),
+    ));
+}
+
+fn toggle_item_labels(
+    action_state: Res<ActionState>,
+    mut item_label_settings: ResMut<ItemLabelSettings>,
+){
+    if !action_state.just_pressed(InputAction::ToggleLabels) {return;}
+    item_label_settings.enabled = !item_label_settings.enabled;
+}
+
+fn update_item_label(
+    time: Res<Time>,
+    item_label_settings: Res<ItemLabelSettings>,
+    hover_state: Res<HoverState>,
+    item_catalog: Res<ItemCatalog>,
+    hand_q: Query<&Hand>,
+    item_q: Query<(&Item, &Transform, &Sprite), Without<ItemLabel>>,
+    mut label_q: Query<(&mut ItemLabel, &mut Text2d, &mut Transform, &mut Visibility), Without<ItemLabelDescription>>,
+    mut description_q: Query<&mut Text2d, With<ItemLabelDescription>>,
+){
+    let Ok((mut label, mut name_text, mut label_transform, mut visibility)) = label_q.single_mut() else {return};
+    let Ok(mut description_text) = description_q.single_mut() else {return};
+
+    // a held item wins over whatever the hand is hovering
+    let target = hand_q.single().ok()
+        .and_then(|hand| hand.grabbed_body)
+        .or(hover_state.hovered)
+        .filter(|_| item_label_settings.enabled)
+        .and_then(|entity| Some((entity, item_q.get(entity).ok()?)));
+    let Some((item_entity, (item, item_transform, item_sprite))) = target else {
+        label.item = None;
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    let Some(item_data) = item_catalog.0.get(&item.name) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    // stays upright above the item, the item itself may be spinning
+    let item_height = item_sprite.custom_size.map_or(0.0, |size| size.y);
+    let target_pos = item_transform.translation.truncate() +vec2(0.0, item_height/2.0 +LABEL_MARGIN);
+    let label_z = item_transform.translation.z + LABEL_Z_OFFSET;
+    if label.item != Some(item_entity) || item_catalog.is_changed() {
+        name_text.0 = item_data.display_name.clone();
+        description_text.0 = item_data.description.clone();
+    }
+    if label.item != Some(item_entity) {
+        label.item = Some(item_entity);
+        label_transform.translation = target_pos.extend(label_z);
+    } else {
+        let follow = 1.0 - (-LABEL_FOLLOW_SPEED * time.delta_secs()).exp();
+        let pos = label_transform.translation.truncate().lerp(target_pos, follow);
+        label_transform.translation = pos.extend(label_z);
+    }
+    *visibility = Visibility::Visible;
+}
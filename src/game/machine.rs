@@ -1,55 +1,355 @@
 use bevy::prelude::*;
-use bevy_rapier2d::prelude::{Collider, Sensor};
+use bevy_rapier2d::prelude::{ActiveEvents, Collider, CollisionEvent, ExternalImpulse, Sensor};
 
-use crate::game::{interactable::{Interactable, InteractionType}, recipes::Recipes};
+use crate::game::{hammer::MachineHit, hand::{Hand, NotGrabbable}, interactable::{AcceptsItems, ClickSource, Clicked, Interactable, ItemDropped}, item::{Item, ItemCatalog, spawn_item}, recipes::Recipes, states::GameplaySet};
 
 pub struct MachinePlugin;
 impl Plugin for MachinePlugin{
     fn build(&self, app: &mut App) {
-        
+        app.add_message::<RecipeCompleted>();
+        app.add_message::<RecipeFailed>();
+        app.add_message::<EjectMachineContents>();
+        app.add_message::<ItemInsertedIntoMachine>();
+        app.add_observer(on_item_dropped);
+        app.add_observer(on_machine_clicked);
+        app.add_systems(Update, (
+            handle_thrown_items,
+            handle_machine_hits,
+            handle_production,
+            handle_ejecting,
+            update_machine_sprites,
+            update_machine_acceptance,
+        ).chain().in_set(GameplaySet));
     }
 }
 
 const MACHIEN_MAX_HP: i32 = 5;
 const MACHINE_CAPACITY: usize = 5;
+const MACHINE_SIZE: Vec2 = vec2(128.0, 128.0);
+// the solid hull is smaller than the sensor, so thrown items reach the sensor before bouncing off
+const MACHINE_HULL_INSET: f32 = 16.0;
+const MACHINE_OUTPUT_OFFSET: Vec2 = vec2(96.0, 32.0);
+const MACHINE_OUTPUT_SPACING: f32 = 40.0;
+const MACHINE_REJECT_IMPULSE: f32 = 400.0;
+// hits harder than this break the machine instead of counting towards crafting
+const MACHINE_OVERHIT_FORCE: f32 = 40000.0;
 
 #[derive(Component)]
 pub struct Machine{
     pub items: Vec<String>,
     pub capacity: usize,
+    // every hit on a loaded machine takes one off, production runs when it reaches 0.
+    // while broken, hits count it back up until the machine is repaired
     pub hp: i32,
+    pub broken: bool,
+    pub output_offset: Vec2,
 }
 
-fn handle_production(
+impl Machine{
+    pub fn has_space(&self) -> bool{
+        self.items.len() < self.capacity
+    }
+}
+
+// solid body of a machine, the Machine itself only has a sensor
+#[derive(Component)]
+pub struct MachineHull(pub Entity);
+
+#[derive(Message, Debug, Clone)]
+pub struct RecipeCompleted{
+    pub machine: Entity,
+    // results and byproducts, one entry per spawned item
+    pub outputs: Vec<String>,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct RecipeFailed{
+    pub machine: Entity,
+    pub ingredients: Vec<String>,
+    pub output: String,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct EjectMachineContents(pub Entity);
+
+#[derive(Message, Debug, Clone)]
+pub struct ItemInsertedIntoMachine{
+    pub machine: Entity,
+    pub item: String,
+}
+
+fn on_item_dropped(
+    item_dropped: On<ItemDropped>,
+    mut cmds: Commands,
     mut machine_q: Query<(&mut Machine, &Transform)>,
-    recipes: Res<Recipes>
+    item_q: Query<(&Item, &Transform)>,
+    mut item_inserted_msgs: MessageWriter<ItemInsertedIntoMachine>,
+){
+    let Ok((mut machine, machine_transform)) = machine_q.get_mut(item_dropped.target) else {return};
+    let Ok((item, item_transform)) = item_q.get(item_dropped.item) else {return};
+
+    if machine.has_space(){
+        machine.items.push(item.name.clone());
+        // the hand notices the despawn and lets go on its own
+        cmds.entity(item_dropped.item).despawn();
+        item_inserted_msgs.write(ItemInsertedIntoMachine {
+            machine: item_dropped.target,
+            item: item.name.clone(),
+        });
+        return;
+    }
+
+    // full machines spit the item back out
+    let away = (item_transform.translation - machine_transform.translation).truncate().normalize_or(Vec2::Y);
+    cmds.entity(item_dropped.item).insert(ExternalImpulse {
+        impulse: (away +Vec2::Y).normalize() * MACHINE_REJECT_IMPULSE,
+        torque_impulse: 0.0,
+    });
+}
+
+// items that fly into a machine go in as if they had been dropped there.
+// held items are left to the hand, it drops them on release
+fn handle_thrown_items(
+    mut cmds: Commands,
+    mut collision_msgs: MessageReader<CollisionEvent>,
+    machine_q: Query<(), With<Machine>>,
+    item_q: Query<(), With<Item>>,
+    hand_q: Query<&Hand>,
+){
+    let held_entity = hand_q.single().ok().and_then(|hand| hand.grabbed_body);
+    for collision in collision_msgs.read(){
+        let CollisionEvent::Started(entity_a, entity_b, _) = collision else {continue;};
+        let (machine_entity, item_entity) = if machine_q.contains(*entity_a) {(*entity_a, *entity_b)} else {(*entity_b, *entity_a)};
+        if !machine_q.contains(machine_entity) || !item_q.contains(item_entity) {continue;}
+        if held_entity == Some(item_entity) {continue;}
+        cmds.trigger(ItemDropped { target: machine_entity, item: item_entity });
+    }
+}
+
+fn on_machine_clicked(
+    clicked: On<Clicked>,
+    machine_q: Query<(), With<Machine>>,
+    mut eject_machine_contents_msgs: MessageWriter<EjectMachineContents>,
+){
+    // a missed grab should not empty the machine
+    if clicked.source != ClickSource::Interact {return;}
+    if !machine_q.contains(clicked.target) {return;}
+    eject_machine_contents_msgs.write(EjectMachineContents(clicked.target));
+}
+
+fn handle_machine_hits(
+    mut machine_hit_msgs: MessageReader<MachineHit>,
+    mut machine_q: Query<&mut Machine>,
+){
+    for machine_hit in machine_hit_msgs.read(){
+        let Ok(mut machine) = machine_q.get_mut(machine_hit.machine) else {continue;};
+
+        if machine.broken {
+            machine.hp += 1;
+            if machine.hp >= MACHIEN_MAX_HP {
+                machine.hp = MACHIEN_MAX_HP;
+                machine.broken = false;
+                info!("machine {:?} repaired", machine_hit.machine);
+            }
+            continue;
+        }
+
+        if machine_hit.force >= MACHINE_OVERHIT_FORCE {
+            machine.hp = 0;
+            machine.broken = true;
+            info!("machine {:?} broke", machine_hit.machine);
+            continue;
+        }
+        // nothing to craft, so the hit does nothing
+        if machine.items.is_empty() {continue;}
+
+        machine.hp = (machine.hp - 1).max(0);
+    }
+}
+
+fn handle_production(
+    mut cmds: Commands,
+    mut machine_q: Query<(Entity, &mut Machine, &Transform)>,
+    recipes: Res<Recipes>,
+    item_catalog: Res<ItemCatalog>,
+    mut recipe_completed_msgs: MessageWriter<RecipeCompleted>,
+    mut recipe_failed_msgs: MessageWriter<RecipeFailed>,
 ){
-    let Ok((mut machine, machine_transform)) = machine_q.single_mut() else {return};
+    for (machine_entity, mut machine, machine_transform) in machine_q.iter_mut(){
+        if machine.broken || machine.hp > 0 {continue;}
+        machine.hp = MACHIEN_MAX_HP;
 
-    let ingredients = machine.items.clone();
+        if machine.items.is_empty() {continue;}
+        let output_pos = machine_transform.translation.truncate() +machine.output_offset;
 
-    if let Some(result_item_name) = recipes.check_machine(ingredients) {
-        info!("Rezept gefunden! Erstelle: {}", result_item_name);
-        // Hier spawn_item() aufrufen...
+        let Some(recipe) = recipes.check_machine(&machine.items) else {
+            debug!("no recipe for {:?}", machine.items);
+            spawn_item(&mut cmds, &item_catalog, output_pos, recipes.failure_item.clone());
+            recipe_failed_msgs.write(RecipeFailed {
+                machine: machine_entity,
+                ingredients: std::mem::take(&mut machine.items),
+                output: recipes.failure_item.clone(),
+            });
+            continue;
+        };
+
+        let outputs: Vec<String> = recipe.outputs().cloned().collect();
+        debug!("recipe matched, producing {:?}", outputs);
+        for (i, output) in outputs.iter().enumerate(){
+            let pos = output_pos +vec2(0.0, i as f32 * MACHINE_OUTPUT_SPACING);
+            spawn_item(&mut cmds, &item_catalog, pos, output.clone());
+        }
         machine.items.clear();
+
+        recipe_completed_msgs.write(RecipeCompleted {
+            machine: machine_entity,
+            outputs,
+        });
+    }
+}
+
+fn handle_ejecting(
+    mut cmds: Commands,
+    mut eject_machine_contents_msgs: MessageReader<EjectMachineContents>,
+    mut machine_q: Query<(&mut Machine, &Transform)>,
+    item_catalog: Res<ItemCatalog>,
+){
+    for eject_machine_contents in eject_machine_contents_msgs.read(){
+        let Ok((mut machine, machine_transform)) = machine_q.get_mut(eject_machine_contents.0) else {continue;};
+        if machine.items.is_empty() {continue;}
+
+        let output_pos = machine_transform.translation.truncate() +machine.output_offset;
+        for (i, item_name) in std::mem::take(&mut machine.items).into_iter().enumerate(){
+            let pos = output_pos +vec2(0.0, i as f32 * MACHINE_OUTPUT_SPACING);
+            spawn_item(&mut cmds, &item_catalog, pos, item_name);
+        }
+        if !machine.broken {
+            machine.hp = MACHIEN_MAX_HP;
+        }
+    }
+}
+
+fn update_machine_sprites(
+    mut machine_q: Query<(&Machine, &mut Sprite), Changed<Machine>>,
+){
+    for (machine, mut sprite) in machine_q.iter_mut(){
+        let health = machine.hp as f32 / MACHIEN_MAX_HP as f32;
+        sprite.color = if machine.broken {
+            Color::linear_rgb(0.2, 0.2, 0.2)
+        } else {
+            Color::linear_rgb(1.0, health, health)
+        };
+    }
+}
+
+fn update_machine_acceptance(
+    mut machine_q: Query<(&Machine, &mut AcceptsItems), Changed<Machine>>,
+){
+    for (machine, mut accepts_items) in machine_q.iter_mut(){
+        accepts_items.set_if_neq(if machine.has_space() {AcceptsItems::Any} else {AcceptsItems::Nothing});
     }
 }
 
 pub fn spawn_machine(cmds: &mut Commands, pos: Vec2) {
-    cmds.spawn((
+    let machine_entity = cmds.spawn((
         Machine{
             items: Vec::new(),
             capacity: MACHINE_CAPACITY,
-            hp: MACHIEN_MAX_HP
+            hp: MACHIEN_MAX_HP,
+            broken: false,
+            output_offset: MACHINE_OUTPUT_OFFSET,
         },
-        Interactable {interaction_type: InteractionType::Machine},
-        Sprite::default(),
+        Interactable,
+        AcceptsItems::Any,
+        Sprite::from_color(Color::WHITE, MACHINE_SIZE),
         Transform::from_xyz(pos.x, pos.y, 0.0),
-        Collider::cuboid(64.0, 64.0),
-        Sensor
-    ));
+        Collider::cuboid(MACHINE_SIZE.x/2.0, MACHINE_SIZE.y/2.0),
+        Sensor,
+        ActiveEvents::COLLISION_EVENTS,
+    )).id();
+    let hull_size = MACHINE_SIZE -Vec2::splat(2.0 * MACHINE_HULL_INSET);
     cmds.spawn((
+        MachineHull(machine_entity),
+        NotGrabbable,
         Transform::from_xyz(pos.x, pos.y, 0.0),
-        Collider::cuboid(64.0, 64.0),
+        Collider::cuboid(hull_size.x/2.0, hull_size.y/2.0),
     ));
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::{message::Messages, system::RunSystemOnce}, platform::collections::HashMap};
+
+    use super::*;
+    use crate::game::item::{ItemData, ItemSpawned};
+
+    fn machine(items: &[&str]) -> Machine {
+        Machine {
+            items: items.iter().map(|name| name.to_string()).collect(),
+            capacity: MACHINE_CAPACITY,
+            hp: MACHIEN_MAX_HP,
+            broken: false,
+            output_offset: MACHINE_OUTPUT_OFFSET,
+        }
+    }
+
+    fn item_data() -> ItemData {
+        ItemData {
+            display_name: String::new(),
+            description: String::new(),
+            sprite: Sprite::default(),
+            collider: Collider::ball(1.0),
+            mass: 1.0,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn machine_holds_exactly_its_capacity() {
+        let mut machine = machine(&[]);
+        for _ in 0..MACHINE_CAPACITY{
+            assert!(machine.has_space());
+            machine.items.push("red".to_string());
+        }
+        assert!(!machine.has_space());
+    }
+
+    #[test]
+    fn ejecting_respawns_every_stored_item_next_to_the_machine() {
+        let mut world = World::new();
+        world.init_resource::<Messages<EjectMachineContents>>();
+        world.init_resource::<Messages<ItemSpawned>>();
+        world.insert_resource(ItemCatalog(HashMap::from_iter([
+            ("red".to_string(), item_data()),
+            ("ice".to_string(), item_data()),
+        ])));
+        let machine_entity = world.spawn((machine(&["red", "ice", "red"]), Transform::from_xyz(100.0, 0.0, 0.0))).id();
+        world.write_message(EjectMachineContents(machine_entity));
+        world.run_system_once(handle_ejecting).unwrap();
+
+        assert!(world.get::<Machine>(machine_entity).unwrap().items.is_empty());
+        let mut ejected: Vec<(String, Vec2)> = world.query::<(&Item, &Transform)>().iter(&world)
+            .map(|(item, transform)| (item.name.clone(), transform.translation.truncate()))
+            .collect();
+        ejected.sort_by(|(_, pos_a), (_, pos_b)| pos_a.y.total_cmp(&pos_b.y));
+        let output_pos = vec2(100.0, 0.0) +MACHINE_OUTPUT_OFFSET;
+        assert_eq!(ejected, vec![
+            ("red".to_string(), output_pos),
+            ("ice".to_string(), output_pos +vec2(0.0, MACHINE_OUTPUT_SPACING)),
+            ("red".to_string(), output_pos +vec2(0.0, 2.0 * MACHINE_OUTPUT_SPACING)),
+        ]);
+    }
+
+    #[test]
+    fn ejecting_an_empty_machine_spawns_nothing() {
+        let mut world = World::new();
+        world.init_resource::<Messages<EjectMachineContents>>();
+        world.init_resource::<Messages<ItemSpawned>>();
+        world.insert_resource(ItemCatalog::default());
+        let machine_entity = world.spawn((machine(&[]), Transform::default())).id();
+        world.write_message(EjectMachineContents(machine_entity));
+        world.run_system_once(handle_ejecting).unwrap();
+
+        assert_eq!(world.query::<&Item>().iter(&world).count(), 0);
+    }
+}
@@ -1,31 +1,55 @@
 use bevy::prelude::*;
 
+pub mod button;
 pub mod camera;
+pub mod dialogue;
+pub mod hammer;
 pub mod hand;
+pub mod hover;
+pub mod input;
 pub mod interactable;
 pub mod item;
+pub mod labels;
 pub mod machine;
 pub mod npc;
 pub mod recipes;
 pub mod rooms;
+pub mod score;
+pub mod settings;
+pub mod slap;
 pub mod spawner;
 pub mod states;
+pub mod touch;
+pub mod trash_bin;
 
 
 pub struct GamePlugin;
 impl Plugin for GamePlugin{
     fn build(&self, app: &mut App) {
         app.add_plugins((
+            button::ButtonPlugin,
             camera::CameraPlugin,
+            dialogue::DialoguePlugin,
+            hammer::HammerPlugin,
             hand::HandPlugin,
+            hover::HoverPlugin,
+            input::InputActionPlugin,
             interactable::InteractablePlugin,
             item::ItemPlugin,
+            labels::ItemLabelPlugin,
             machine::MachinePlugin,
             npc::NPCPlugin,
+        ));
+        app.add_plugins((
             recipes::RecepiePlugin,
             rooms::RoomPlugin,
+            score::ScorePlugin,
+            settings::SettingsPlugin,
+            slap::SlapPlugin,
             spawner::SpawnerPlugin,
             states::StatesPlugin,
+            touch::TouchInputPlugin,
+            trash_bin::TrashBinPlugin,
         ));
     }
 }
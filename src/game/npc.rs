@@ -1,42 +1,377 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::{Collider, Sensor};
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
 
-use crate::game::{interactable::{Interactable, InteractionType}, item::{Item}};
+use crate::game::{dialogue::Dialogue, interactable::{AcceptsItems, Interactable, ItemDropped}, item::{Item, ItemCatalog}, recipes::Recipes, rooms::{FLOOR_HEIGHT, FLOOR_WIDTH, ROOM_SIZE}, slap::Slapped, states::GameplaySet};
 
 pub struct NPCPlugin;
 
 impl Plugin for NPCPlugin {
     fn build(&self, app: &mut App) {
+        app.add_message::<OrderDelivered>();
+        app.add_message::<OrderFailed>();
+        app.add_observer(on_item_dropped);
+        app.insert_resource(CustomerSpawner {
+            timer: Timer::from_seconds(CUSTOMER_SPAWN_DELAY_SECS, TimerMode::Once),
+        });
+        app.add_systems(Update, (
+            spawn_customers,
+            handle_delivered_orders,
+            handle_failed_orders,
+            handle_slapped_customers,
+            handle_impatient_customers,
+            move_customers,
+            update_customer_acceptance,
+            update_order_bubbles,
+        ).chain().in_set(GameplaySet));
     }
 }
 
+const CUSTOMER_SPAWN_DELAY_SECS: f32 = 2.0;
+const CUSTOMER_SPEED: f32 = 200.0;
+const CUSTOMER_KEY_CHANCE: f64 = 0.2;
+const CUSTOMER_POINTS: f32 = 10.0;
+const CUSTOMER_DISGUSTED_COLOR: Color = Color::linear_rgb(0.5, 0.6, 0.2);
+const CUSTOMER_SLAPPED_COLOR: Color = Color::linear_rgb(0.8, 0.3, 0.3);
+const CUSTOMER_START_MOOD: i32 = 3;
+// slaps at least this strong make a customer give up on their order at once
+const CUSTOMER_STORM_OFF_SLAP: f32 = 5000.0;
+// how long a customer waits at the counter before giving up on their order
+const CUSTOMER_PATIENCE_SECS: f32 = 90.0;
+const CUSTOMER_IMPATIENT_COLOR: Color = Color::linear_rgb(0.4, 0.4, 0.6);
+// customers only order items with this tag, raw ingredients come straight from the dispenser
+const ORDER_ITEM_TAG: &str = "result";
+// (name, dialogue)
+const CUSTOMERS: [(&str, &str); 5] = [
+    ("Gerald", "data/dialogue/gerald.dialogue.ron"),
+    ("Mrs. Spoon", "data/dialogue/mrs_spoon.dialogue.ron"),
+    ("The Moth", "data/dialogue/the_moth.dialogue.ron"),
+    ("Uncle Fridge", "data/dialogue/uncle_fridge.dialogue.ron"),
+    ("???", "data/dialogue/unknown.dialogue.ron"),
+];
+
 #[derive(Component)]
 pub struct NPC{
     pub name: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
 pub enum Reward{
     Points(f32),
     Key,
 }
 
+#[derive(Component)]
+pub struct Quest{
+    pub wants: String,
+    pub reward: Reward,
+}
+
+#[derive(Component)]
+pub struct Customer{
+    pub state: CustomerState,
+    pub mood: i32,
+    pub counter_pos: Vec2,
+    pub door_pos: Vec2,
+    // runs while waiting at the counter
+    pub patience: Timer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomerState{
+    Arriving,
+    Waiting,
+    Leaving,
+}
+
+// shows the wanted item above a waiting customer
+#[derive(Component)]
+struct OrderBubble;
+
+#[derive(Resource)]
+struct CustomerSpawner{
+    timer: Timer,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct OrderDelivered{
+    pub npc: Entity,
+    pub item: String,
+    pub reward: Reward,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct OrderFailed{
+    pub npc: Entity,
+    pub item: String,
+}
+
 pub fn spawn_npc(
     cmds: &mut Commands,
     position: Vec2,
     npc_name: String,
     wants: String,
     reward: Reward
-){
+) -> Entity{
     cmds.spawn((
         NPC {
             name: npc_name,
         },
-        Interactable {interaction_type: InteractionType::Quest { wants, reward }},
-        Interactable {interaction_type: InteractionType::Talk},
+        Quest { wants, reward },
+        Interactable,
         Transform::from_xyz(position.x, position.y, 0.0),
         Sprite::from_color(Color::linear_rgb(0.3, 0.3, 0.3), vec2(64.0, 128.0)),
         Collider::cuboid(32.0, 64.0),
         Sensor,
+    )).id()
+}
+
+// customers take what they ordered and complain about anything else
+fn on_item_dropped(
+    item_dropped: On<ItemDropped>,
+    mut cmds: Commands,
+    quest_q: Query<(&Quest, &Customer)>,
+    item_q: Query<&Item>,
+    mut order_delivered_msgs: MessageWriter<OrderDelivered>,
+    mut order_failed_msgs: MessageWriter<OrderFailed>,
+){
+    let Ok((quest, customer)) = quest_q.get(item_dropped.target) else {return};
+    if customer.state != CustomerState::Waiting {return;}
+    let Ok(item) = item_q.get(item_dropped.item) else {return};
+
+    if item.name != quest.wants {
+        order_failed_msgs.write(OrderFailed {
+            npc: item_dropped.target,
+            item: item.name.clone(),
+        });
+        return;
+    }
+    debug!("quest complete, reward: {:?}", quest.reward);
+    order_delivered_msgs.write(OrderDelivered {
+        npc: item_dropped.target,
+        item: item.name.clone(),
+        reward: quest.reward.clone(),
+    });
+    // the hand notices the despawn and lets go on its own
+    cmds.entity(item_dropped.item).despawn();
+}
+
+fn spawn_customers(
+    mut cmds: Commands,
+    time: Res<Time>,
+    mut customer_spawner: ResMut<CustomerSpawner>,
+    customer_q: Query<(), With<Customer>>,
+    recipes: Res<Recipes>,
+    item_catalog: Res<ItemCatalog>,
+    asset_server: Res<AssetServer>,
+){
+    if !customer_q.is_empty() {
+        customer_spawner.timer.reset();
+        return;
+    }
+    // the timer only fires once, so wait for the data files before letting it run
+    if recipes.recipes.is_empty() || item_catalog.0.is_empty() {return;}
+    if !customer_spawner.timer.tick(time.delta()).just_finished() {return;}
+
+    let mut rng = rand::rng();
+    let mut orders: Vec<&String> = recipes.results()
+        .filter(|name| recipes.reachable.contains(*name))
+        .filter(|name| item_catalog.0.get(*name).is_some_and(|item_data| item_data.has_tag(ORDER_ITEM_TAG)))
+        .collect();
+    orders.sort();
+    orders.dedup();
+    let Some(wants) = orders.choose(&mut rng).map(|wants| (*wants).clone()) else {
+        warn!("no recipe makes an item customers could order");
+        customer_spawner.timer.reset();
+        return;
+    };
+    let Some((name, dialogue_path)) = CUSTOMERS.choose(&mut rng) else {return;};
+    let reward = if rand::random_bool(CUSTOMER_KEY_CHANCE) {Reward::Key} else {Reward::Points(CUSTOMER_POINTS)};
+
+    // customers walk in from the far side of the counter room
+    let ground_y = -ROOM_SIZE.y/2.0 +FLOOR_HEIGHT +FLOOR_WIDTH/2.0 +64.0;
+    let door_pos = vec2(-ROOM_SIZE.x*1.5 -64.0, ground_y);
+    let counter_pos = vec2(-ROOM_SIZE.x +ROOM_SIZE.x/4.0, ground_y);
+
+    let order_sprite = item_catalog.0.get(&wants).map(|item_data| item_data.sprite.clone());
+    let npc_entity = spawn_npc(&mut cmds, door_pos, name.to_string(), wants, reward);
+    cmds.entity(npc_entity).insert((
+        Customer {
+            state: CustomerState::Arriving,
+            mood: CUSTOMER_START_MOOD,
+            counter_pos,
+            door_pos,
+            patience: Timer::from_seconds(CUSTOMER_PATIENCE_SECS, TimerMode::Once),
+        },
+        Dialogue(asset_server.load(*dialogue_path)),
     ));
-    
+    if let Some(order_sprite) = order_sprite {
+        cmds.entity(npc_entity).with_child((
+            OrderBubble,
+            order_sprite,
+            Transform::from_xyz(0.0, 96.0, 0.1),
+            Visibility::Hidden,
+        ));
+    }
+}
+
+fn send_customer_away(
+    cmds: &mut Commands,
+    customer_entity: Entity,
+    customer: &mut Customer,
+    children: Option<&Children>,
+    order_bubble_q: &Query<(), With<OrderBubble>>,
+){
+    cmds.entity(customer_entity).remove::<Quest>();
+    customer.state = CustomerState::Leaving;
+
+    for child in children.into_iter().flatten(){
+        if order_bubble_q.contains(*child) {
+            cmds.entity(*child).despawn();
+        }
+    }
+}
+
+fn handle_delivered_orders(
+    mut cmds: Commands,
+    mut order_delivered_msgs: MessageReader<OrderDelivered>,
+    mut customer_q: Query<(&mut Customer, Option<&Children>)>,
+    order_bubble_q: Query<(), With<OrderBubble>>,
+){
+    for order_delivered in order_delivered_msgs.read(){
+        let Ok((mut customer, children)) = customer_q.get_mut(order_delivered.npc) else {continue;};
+        send_customer_away(&mut cmds, order_delivered.npc, &mut customer, children, &order_bubble_q);
+    }
+}
+
+// customers put up with wrong orders, but being handed the failed-recipe output makes them storm off
+fn handle_failed_orders(
+    mut cmds: Commands,
+    mut order_failed_msgs: MessageReader<OrderFailed>,
+    mut customer_q: Query<(&mut Customer, &mut Sprite, Option<&Children>)>,
+    order_bubble_q: Query<(), With<OrderBubble>>,
+    recipes: Res<Recipes>,
+){
+    for order_failed in order_failed_msgs.read(){
+        if order_failed.item != recipes.failure_item {continue;}
+        let Ok((mut customer, mut sprite, children)) = customer_q.get_mut(order_failed.npc) else {continue;};
+        if customer.state != CustomerState::Waiting {continue;}
+
+        info!("customer {:?} was handed trash and leaves", order_failed.npc);
+        sprite.color = CUSTOMER_DISGUSTED_COLOR;
+        send_customer_away(&mut cmds, order_failed.npc, &mut customer, children, &order_bubble_q);
+    }
+}
+
+// every slap sours the mood and halves the points on offer, too many and they leave
+fn handle_slapped_customers(
+    mut cmds: Commands,
+    mut slapped_msgs: MessageReader<Slapped>,
+    mut customer_q: Query<(&mut Customer, &mut Sprite, Option<&mut Quest>, Option<&Children>)>,
+    order_bubble_q: Query<(), With<OrderBubble>>,
+){
+    for slapped in slapped_msgs.read(){
+        let Ok((mut customer, mut sprite, quest, children)) = customer_q.get_mut(slapped.target) else {continue;};
+        if customer.state == CustomerState::Leaving {continue;}
+
+        customer.mood -= 1;
+        sprite.color = CUSTOMER_SLAPPED_COLOR;
+        if let Some(mut quest) = quest {
+            if let Reward::Points(points) = quest.reward {
+                quest.reward = Reward::Points(points / 2.0);
+            }
+        }
+        info!("customer {:?} got slapped, mood {}", slapped.target, customer.mood);
+
+        if customer.mood <= 0 || slapped.strength >= CUSTOMER_STORM_OFF_SLAP {
+            send_customer_away(&mut cmds, slapped.target, &mut customer, children, &order_bubble_q);
+        }
+    }
+}
+
+// nobody waits forever, so an order the player cannot make does not block the counter
+fn handle_impatient_customers(
+    mut cmds: Commands,
+    time: Res<Time>,
+    mut customer_q: Query<(Entity, &mut Customer, &mut Sprite, Option<&Children>)>,
+    order_bubble_q: Query<(), With<OrderBubble>>,
+){
+    for (customer_entity, mut customer, mut sprite, children) in customer_q.iter_mut(){
+        if customer.state != CustomerState::Waiting {continue;}
+        if !customer.patience.tick(time.delta()).just_finished() {continue;}
+
+        info!("customer {:?} got tired of waiting and leaves", customer_entity);
+        sprite.color = CUSTOMER_IMPATIENT_COLOR;
+        send_customer_away(&mut cmds, customer_entity, &mut customer, children, &order_bubble_q);
+    }
+}
+
+fn update_customer_acceptance(
+    mut customer_q: Query<(&Customer, Option<&Quest>, &mut AcceptsItems)>,
+){
+    for (customer, quest, mut accepts_items) in customer_q.iter_mut(){
+        let wants = quest.filter(|_| customer.state == CustomerState::Waiting);
+        accepts_items.set_if_neq(match wants {
+            Some(quest) => AcceptsItems::Only(quest.wants.clone()),
+            None => AcceptsItems::Nothing,
+        });
+    }
+}
+
+// dialogue can change what a customer wants after they arrived
+fn update_order_bubbles(
+    customer_q: Query<(&Quest, &Children), (With<Customer>, Changed<Quest>)>,
+    mut order_bubble_q: Query<&mut Sprite, With<OrderBubble>>,
+    item_catalog: Res<ItemCatalog>,
+){
+    for (quest, children) in customer_q.iter(){
+        let Some(item_data) = item_catalog.0.get(&quest.wants) else {continue;};
+        for child in children{
+            if let Ok(mut sprite) = order_bubble_q.get_mut(*child) {
+                *sprite = item_data.sprite.clone();
+            }
+        }
+    }
+}
+
+fn move_customers(
+    mut cmds: Commands,
+    time: Res<Time>,
+    mut customer_q: Query<(Entity, &mut Customer, &mut Transform, Option<&Children>)>,
+    mut order_bubble_q: Query<&mut Visibility, With<OrderBubble>>,
+){
+    for (customer_entity, mut customer, mut transform, children) in customer_q.iter_mut(){
+        let target = match customer.state {
+            CustomerState::Arriving => customer.counter_pos,
+            CustomerState::Waiting => continue,
+            CustomerState::Leaving => customer.door_pos,
+        };
+
+        let pos = transform.translation.truncate();
+        let step = CUSTOMER_SPEED * time.delta_secs();
+        if pos.distance(target) > step {
+            let new_pos = pos.move_towards(target, step);
+            transform.translation.x = new_pos.x;
+            transform.translation.y = new_pos.y;
+            continue;
+        }
+
+        transform.translation.x = target.x;
+        transform.translation.y = target.y;
+        match customer.state {
+            CustomerState::Arriving => {
+                customer.state = CustomerState::Waiting;
+                for child in children.into_iter().flatten(){
+                    if let Ok(mut visibility) = order_bubble_q.get_mut(*child) {
+                        *visibility = Visibility::Inherited;
+                    }
+                }
+            }
+            CustomerState::Leaving => {
+                cmds.entity(customer_entity).despawn();
+            }
+            CustomerState::Waiting => {}
+        }
+    }
 }
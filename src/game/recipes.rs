@@ -1,59 +1,483 @@
-use std::{arch::x86_64::_CMP_ORD_S, collections::{BTreeSet, btree_set}};
+use std::collections::BTreeMap;
 
-use bevy::{platform::collections::HashMap, prelude::*};
-use bevy_rapier2d::prelude::Collider;
+use bevy::{asset::{AssetLoader, LoadContext, io::Reader}, platform::collections::{HashMap, HashSet}, prelude::*};
+use serde::Deserialize;
+use thiserror::Error;
 
-use crate::game::item::{ItemData, ItemSetupSet};
+use crate::game::{item::{ItemCatalog, ItemSetupSet}, spawner::ItemSpawner};
 
 pub struct RecepiePlugin;
 impl Plugin for RecepiePlugin{
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_ingriedients_results_recepies.in_set(ItemSetupSet));
+        app.init_asset::<RecipeBookAsset>();
+        app.register_asset_loader(RecipeBookLoader);
+        app.init_resource::<Recipes>();
+        app.add_systems(Startup, setup_recipe_book.in_set(ItemSetupSet));
+        app.add_systems(Update, update_recipes);
     }
 } 
 
+// run with `--features dev` to hot reload this file
+const RECIPE_BOOK_PATH: &str = "data/kitchen.recipes.ron";
+const DEFAULT_FAILURE_ITEM: &str = "trash";
 
 
-// #[derive(Resource, Default)]
-// pub struct Ingriedients(pub HashMap<String, ItemData>);
+// (item name, count)
+pub type ItemStack = (String, u32);
 
-// #[derive(Resource, Default)]
-// pub struct Results(pub Vec<>);
+pub struct Recipe {
+    pub ingredients: Vec<ItemStack>,
+    // ordered recipes need the ingredients in exactly this insertion order
+    pub ordered: bool,
+    pub results: Vec<ItemStack>,
+    pub byproducts: Vec<ItemStack>,
+}
 
+impl Recipe {
+    pub fn matches(&self, machine_ingredients: &[String]) -> bool {
+        if self.ordered {
+            expand_stacks(&self.ingredients).eq(machine_ingredients.iter())
+        } else {
+            count_items(expand_stacks(&self.ingredients)) == count_items(machine_ingredients.iter())
+        }
+    }
 
-#[derive(Resource, Default)]
-pub struct Recipes(pub HashMap<BTreeSet<String>, String>);// ingriedients, result
+    pub fn outputs(&self) -> impl Iterator<Item = &String> {
+        expand_stacks(&self.results).chain(expand_stacks(&self.byproducts))
+    }
+}
+
+fn expand_stacks(stacks: &[ItemStack]) -> impl Iterator<Item = &String> {
+    stacks.iter().flat_map(|(name, count)| std::iter::repeat_n(name, *count as usize))
+}
+
+fn count_items<'a>(items: impl Iterator<Item = &'a String>) -> BTreeMap<&'a String, u32> {
+    let mut counts = BTreeMap::new();
+    for item in items{
+        *counts.entry(item).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[derive(Resource)]
+pub struct Recipes{
+    pub recipes: Vec<Recipe>,
+    // what a machine spits out when its contents match no recipe
+    pub failure_item: String,
+    // items the player can get hold of with the current spawners
+    pub reachable: HashSet<String>,
+}
+
+impl Default for Recipes {
+    fn default() -> Self {
+        Recipes {
+            recipes: Vec::new(),
+            failure_item: DEFAULT_FAILURE_ITEM.to_string(),
+            reachable: HashSet::new(),
+        }
+    }
+}
 
 impl Recipes {
-    pub fn check_machine(&self, machine_ingredients: Vec<String>) -> Option<&String> {
-        // Konvertierung in ein Set sortiert automatisch und entfernt Duplikate
-        let set: BTreeSet<String> = machine_ingredients.into_iter().collect();
-        self.0.get(&set)
+    pub fn check_machine(&self, machine_ingredients: &[String]) -> Option<&Recipe> {
+        // ordered recipes are more specific, so they win over unordered ones with the same items
+        self.recipes.iter().filter(|recipe| recipe.ordered)
+            .chain(self.recipes.iter().filter(|recipe| !recipe.ordered))
+            .find(|recipe| recipe.matches(machine_ingredients))
+    }
+
+    pub fn results(&self) -> impl Iterator<Item = &String> {
+        self.recipes.iter().flat_map(|recipe| recipe.results.iter().map(|(name, _)| name))
+    }
+}
+
+#[derive(Resource)]
+pub struct RecipeBookHandle(pub Handle<RecipeBookAsset>);
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct RecipeBookAsset {
+    pub recipes: Vec<RecipeDefinition>,
+    #[serde(default = "default_failure_item")]
+    pub failure_item: String,
+}
+
+fn default_failure_item() -> String {DEFAULT_FAILURE_ITEM.to_string()}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RecipeDefinition {
+    pub ingredients: Vec<ItemStack>,
+    #[serde(default)]
+    pub ordered: bool,
+    pub results: Vec<ItemStack>,
+    #[serde(default)]
+    pub byproducts: Vec<ItemStack>,
+}
+
+impl RecipeDefinition {
+    fn name(&self) -> String {
+        self.results.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(" + ")
+    }
+
+    // two recipes with the same key can never be told apart by a machine
+    fn key(&self) -> (bool, Vec<String>) {
+        let mut ingredients: Vec<String> = expand_stacks(&self.ingredients).cloned().collect();
+        if !self.ordered {
+            ingredients.sort();
+        }
+        (self.ordered, ingredients)
+    }
+
+    fn to_recipe(&self) -> Recipe {
+        Recipe {
+            ingredients: self.ingredients.clone(),
+            ordered: self.ordered,
+            results: self.results.clone(),
+            byproducts: self.byproducts.clone(),
+        }
     }
 }
 
-fn setup_ingriedients_results_recepies(
+#[derive(Debug, Error)]
+pub enum RecipeBookLoadError {
+    #[error("could not read recipe book: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse recipe book: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid recipe #{index} for \"{result}\": {reason}")]
+    InvalidRecipe { index: usize, result: String, reason: String },
+}
+
+#[derive(Default, Debug)]
+pub struct RecipeBookReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl RecipeBookAsset {
+    fn validate(&self) -> Result<(), RecipeBookLoadError> {
+        for (index, recipe) in self.recipes.iter().enumerate(){
+            let invalid = |reason: &str| RecipeBookLoadError::InvalidRecipe {
+                index,
+                result: recipe.name(),
+                reason: reason.to_string(),
+            };
+
+            if recipe.results.is_empty() {
+                return Err(invalid("needs at least one result"));
+            }
+            if recipe.ingredients.is_empty() {
+                return Err(invalid("needs at least one ingredient"));
+            }
+            let stacks = recipe.ingredients.iter().chain(recipe.results.iter()).chain(recipe.byproducts.iter());
+            for (name, count) in stacks{
+                if name.trim().is_empty() {
+                    return Err(invalid("item names must not be empty"));
+                }
+                if *count == 0 {
+                    return Err(invalid(&format!("count of \"{}\" must be at least 1", name)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // `dispensed` is everything the item spawners hand out
+    pub fn validate_against(&self, item_catalog: &ItemCatalog, dispensed: &HashSet<String>) -> RecipeBookReport {
+        let mut report = RecipeBookReport::default();
+        let mut seen = HashMap::<(bool, Vec<String>), String>::new();
+
+        if !item_catalog.0.contains_key(&self.failure_item) {
+            report.errors.push(format!("failure item \"{}\" is not in the item catalog", self.failure_item));
+        }
+
+        for recipe in self.recipes.iter(){
+            for (ingredient, _) in recipe.ingredients.iter(){
+                if !item_catalog.0.contains_key(ingredient) {
+                    report.errors.push(format!("recipe for \"{}\" uses unknown ingredient \"{}\"", recipe.name(), ingredient));
+                }
+            }
+            for (output, _) in recipe.results.iter().chain(recipe.byproducts.iter()){
+                if !item_catalog.0.contains_key(output) {
+                    report.errors.push(format!("recipe for \"{}\" produces unknown item \"{}\"", recipe.name(), output));
+                }
+            }
+
+            let (ordered, ingredients) = recipe.key();
+            if let Some(other_recipe) = seen.insert((ordered, ingredients.clone()), recipe.name()) {
+                report.errors.push(format!("ingredients {:?} are used by the recipes for \"{}\" and \"{}\"", ingredients, other_recipe, recipe.name()));
+            }
+        }
+
+        let reachable = self.reachable_items(dispensed);
+        for name in item_catalog.0.keys(){
+            if !reachable.contains(name) {
+                report.warnings.push(format!("item \"{}\" can never be obtained", name));
+            }
+        }
+
+        report
+    }
+
+    // the failure item plus everything that can be dispensed or crafted from dispensed items
+    pub fn reachable_items(&self, dispensed: &HashSet<String>) -> HashSet<String> {
+        let mut reachable = dispensed.clone();
+        reachable.insert(self.failure_item.clone());
+        loop {
+            let newly_reachable: Vec<String> = self.recipes.iter()
+                .filter(|recipe| recipe.ingredients.iter().all(|(ingredient, _)| reachable.contains(ingredient)))
+                .flat_map(|recipe| recipe.results.iter().chain(recipe.byproducts.iter()))
+                .map(|(name, _)| name)
+                .filter(|name| !reachable.contains(*name))
+                .cloned()
+                .collect();
+            if newly_reachable.is_empty() {break;}
+            reachable.extend(newly_reachable);
+        }
+        reachable
+    }
+
+    fn to_recipes(&self, dispensed: &HashSet<String>) -> Recipes {
+        Recipes {
+            recipes: self.recipes.iter().map(RecipeDefinition::to_recipe).collect(),
+            failure_item: self.failure_item.clone(),
+            reachable: self.reachable_items(dispensed),
+        }
+    }
+}
+
+#[derive(Default)]
+struct RecipeBookLoader;
+
+impl AssetLoader for RecipeBookLoader {
+    type Asset = RecipeBookAsset;
+    type Settings = ();
+    type Error = RecipeBookLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let recipe_book = ron::de::from_bytes::<RecipeBookAsset>(&bytes)?;
+        recipe_book.validate()?;
+        Ok(recipe_book)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["recipes.ron"]
+    }
+}
+
+fn setup_recipe_book(
     mut cmds: Commands,
     asset_server: Res<AssetServer>
 ){
-    // let mut ingriedients = HashMap::<String, ItemData>::new();
-    // ingriedients.insert("red".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(1.0, 0.0, 0.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    // ingriedients.insert("green".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(0.0, 1.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    // ingriedients.insert("blue".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(0.0, 0.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    
-    // let mut results = HashMap::<String, ItemData>::new();
-    // results.insert("yellow".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(1.0, 1.0, 0.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    // results.insert("violet".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(1.0, 0.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    // results.insert("turkeu".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(0.0, 1.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-    // results.insert("white".to_string(), ItemData::new(Sprite::from_color(Color::linear_rgb(1.0, 1.0, 1.0), vec2(32.0, 32.0)), Collider::cuboid(16.0, 16.0)));
-
-    let mut recipes = HashMap::<BTreeSet<String>, String>::new();
-    recipes.insert(vec!["red".to_string(), "green".to_string()].into_iter().collect(), "yellow".to_string());
-    recipes.insert(vec!["red".to_string(), "blue".to_string()].into_iter().collect(), "violet".to_string());
-    recipes.insert(vec!["green".to_string(), "blue".to_string()].into_iter().collect(), "turkeu".to_string());
-    recipes.insert(vec!["red".to_string(), "green".to_string(), "blue".to_string()].into_iter().collect(), "white".to_string());
-
-    // cmds.insert_resource(Ingriedients(ingriedients));
-    // cmds.insert_resource(Results(results));
-    cmds.insert_resource(Recipes(recipes));
+    cmds.insert_resource(RecipeBookHandle(asset_server.load(RECIPE_BOOK_PATH)));
+}
+
+fn update_recipes(
+    mut asset_events: MessageReader<AssetEvent<RecipeBookAsset>>,
+    recipe_book_handle: Res<RecipeBookHandle>,
+    recipe_book_assets: Res<Assets<RecipeBookAsset>>,
+    asset_server: Res<AssetServer>,
+    item_catalog: Res<ItemCatalog>,
+    item_spawner_q: Query<&ItemSpawner>,
+    mut recipes: ResMut<Recipes>,
+    mut loaded_once: Local<bool>,
+){
+    let recipe_book_changed = asset_events.read()
+        .filter(|asset_event| matches!(asset_event, AssetEvent::LoadedWithDependencies { .. } | AssetEvent::Modified { .. }))
+        .count() > 0;
+
+    // a recipe book that fails to parse at startup would otherwise just never match anything
+    if !*loaded_once {
+        if let Some(bevy::asset::LoadState::Failed(error)) = asset_server.get_load_state(&recipe_book_handle.0) {
+            panic!("recipe book {} failed to load: {}", RECIPE_BOOK_PATH, error);
+        }
+    }
+
+    if !recipe_book_changed && !item_catalog.is_changed() {return;}
+    if item_catalog.0.is_empty() {return;}
+    let Some(recipe_book) = recipe_book_assets.get(&recipe_book_handle.0) else {return;};
+
+    let dispensed: HashSet<String> = item_spawner_q.iter()
+        .flat_map(|item_spawner| item_spawner.item_list.iter().cloned())
+        .collect();
+    let report = recipe_book.validate_against(&item_catalog, &dispensed);
+    for warning in report.warnings.iter(){
+        warn!("recipe book: {}", warning);
+    }
+    if !report.errors.is_empty() {
+        for error in report.errors.iter(){
+            error!("recipe book: {}", error);
+        }
+        if !*loaded_once {
+            panic!("recipe book {} has {} errors", RECIPE_BOOK_PATH, report.errors.len());
+        }
+        warn!("recipe book: keeping the previous recipes until the errors are fixed");
+        return;
+    }
+
+    *recipes = recipe_book.to_recipes(&dispensed);
+    *loaded_once = true;
+    info!("recipe book loaded with {} recipes", recipes.recipes.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rapier2d::prelude::Collider;
+
+    use super::*;
+    use crate::game::item::ItemData;
+
+    fn stacks(items: &[(&str, u32)]) -> Vec<ItemStack> {
+        items.iter().map(|(name, count)| (name.to_string(), *count)).collect()
+    }
+
+    fn names(items: &[&str]) -> Vec<String> {
+        items.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn recipe(ingredients: &[(&str, u32)], ordered: bool, results: &[(&str, u32)]) -> Recipe {
+        Recipe {
+            ingredients: stacks(ingredients),
+            ordered,
+            results: stacks(results),
+            byproducts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn unordered_recipe_ignores_insertion_order_but_not_counts() {
+        let recipe = recipe(&[("ducky", 1), ("ice", 2)], false, &[("frozen_ducky", 1)]);
+        assert!(recipe.matches(&names(&["ice", "ducky", "ice"])));
+        assert!(!recipe.matches(&names(&["ice", "ducky"])));
+        assert!(!recipe.matches(&names(&["ice", "ducky", "ice", "ice"])));
+    }
+
+    #[test]
+    fn ordered_recipe_needs_exact_order() {
+        let recipe = recipe(&[("ducky", 1), ("ice", 2)], true, &[("frozen_ducky", 1)]);
+        assert!(recipe.matches(&names(&["ducky", "ice", "ice"])));
+        assert!(!recipe.matches(&names(&["ice", "ducky", "ice"])));
+    }
+
+    #[test]
+    fn ordered_recipes_win_over_unordered_ones() {
+        let recipes = Recipes {
+            recipes: vec![
+                recipe(&[("red", 1), ("green", 1)], false, &[("yellow", 1)]),
+                recipe(&[("red", 1), ("green", 1)], true, &[("orange", 1)]),
+            ],
+            ..default()
+        };
+        let result = |ingredients: &[&str]| recipes.check_machine(&names(ingredients)).map(|recipe| recipe.results[0].0.clone());
+        assert_eq!(result(&["red", "green"]), Some("orange".to_string()));
+        assert_eq!(result(&["green", "red"]), Some("yellow".to_string()));
+    }
+
+    #[test]
+    fn outputs_expand_results_then_byproducts() {
+        let recipe = Recipe {
+            byproducts: stacks(&[("trash", 1)]),
+            ..recipe(&[("noodle", 3)], false, &[("noodle", 2)])
+        };
+        assert_eq!(recipe.outputs().cloned().collect::<Vec<_>>(), names(&["noodle", "noodle", "trash"]));
+    }
+
+    #[test]
+    fn unmatched_ingredients_fall_back_to_the_failure_item() {
+        let recipe_book: RecipeBookAsset = ron::de::from_str(r#"(
+            recipes: [(ingredients: [("red", 1)], results: [("pink", 1)])],
+        )"#).unwrap();
+        let recipes = recipe_book.to_recipes(&HashSet::new());
+        assert_eq!(recipes.failure_item, DEFAULT_FAILURE_ITEM);
+        assert!(recipes.check_machine(&names(&["blue"])).is_none());
+        assert!(recipes.check_machine(&[]).is_none());
+    }
+
+    fn catalog(items: &[(&str, &str)]) -> ItemCatalog {
+        ItemCatalog(items.iter().map(|(name, tag)| (name.to_string(), ItemData {
+            display_name: name.to_string(),
+            description: String::new(),
+            sprite: Sprite::default(),
+            collider: Collider::ball(1.0),
+            mass: 1.0,
+            tags: vec![tag.to_string()],
+        })).collect())
+    }
+
+    fn dispensed(items: &[&str]) -> HashSet<String> {
+        items.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn recipe_book(source: &str) -> RecipeBookAsset {
+        ron::de::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn consistent_recipe_book_has_no_findings() {
+        let item_catalog = catalog(&[("trash", "trash"), ("red", "ingredient"), ("green", "ingredient"), ("yellow", "")]);
+        let report = recipe_book(r#"(
+            recipes: [(ingredients: [("red", 1), ("green", 1)], results: [("yellow", 1)], byproducts: [("trash", 1)])],
+        )"#).validate_against(&item_catalog, &dispensed(&["red", "green"]));
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    }
+
+    #[test]
+    fn unknown_items_are_errors() {
+        let item_catalog = catalog(&[("red", "ingredient"), ("yellow", "")]);
+        let report = recipe_book(r#"(
+            recipes: [(ingredients: [("red", 1), ("green", 1)], results: [("yellow", 1), ("orange", 1)])],
+            failure_item: "sludge",
+        )"#).validate_against(&item_catalog, &dispensed(&["red"]));
+        assert_eq!(report.errors.len(), 3, "{:?}", report.errors);
+        assert!(report.errors.iter().any(|error| error.contains("\"sludge\"")));
+        assert!(report.errors.iter().any(|error| error.contains("unknown ingredient \"green\"")));
+        assert!(report.errors.iter().any(|error| error.contains("unknown item \"orange\"")));
+    }
+
+    #[test]
+    fn indistinguishable_recipes_are_errors() {
+        let item_catalog = catalog(&[("trash", "trash"), ("red", "ingredient"), ("green", "ingredient"), ("yellow", ""), ("orange", "")]);
+        let report = recipe_book(r#"(
+            recipes: [
+                (ingredients: [("red", 1), ("green", 1)], results: [("yellow", 1)]),
+                (ingredients: [("green", 1), ("red", 1)], results: [("orange", 1)]),
+            ],
+        )"#).validate_against(&item_catalog, &dispensed(&["red", "green"]));
+        assert_eq!(report.errors.len(), 1, "{:?}", report.errors);
+
+        // an ordered recipe can be told apart from an unordered one with the same items
+        let report = recipe_book(r#"(
+            recipes: [
+                (ingredients: [("red", 1), ("green", 1)], results: [("yellow", 1)]),
+                (ingredients: [("red", 1), ("green", 1)], ordered: true, results: [("orange", 1)]),
+            ],
+        )"#).validate_against(&item_catalog, &dispensed(&["red", "green"]));
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+    }
+
+    #[test]
+    fn items_no_spawner_or_recipe_can_reach_are_warnings() {
+        // tagged as an ingredient, but no spawner hands it out
+        let item_catalog = catalog(&[("trash", "trash"), ("red", "ingredient"), ("gold", "ingredient"), ("yellow", ""), ("crown", "")]);
+        let report = recipe_book(r#"(
+            recipes: [
+                (ingredients: [("red", 2)], results: [("yellow", 1)]),
+                (ingredients: [("gold", 1)], results: [("crown", 1)]),
+            ],
+        )"#).validate_against(&item_catalog, &dispensed(&["red"]));
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        let mut warnings = report.warnings.clone();
+        warnings.sort();
+        assert_eq!(warnings, vec![
+            "item \"crown\" can never be obtained".to_string(),
+            "item \"gold\" can never be obtained".to_string(),
+        ]);
+    }
 }
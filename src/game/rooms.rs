@@ -1,18 +1,25 @@
 use std::collections::HashMap;
 
-use bevy::{input::keyboard::KeyboardInput, prelude::*};
+use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-use crate::game::{interactable::{Interactable, InteractionType}, item::{Item, ItemCatalog, ItemSetupSet, spawn_item}, machine::spawn_machine, spawner::spawn_item_spawner};
+use crate::game::{hammer::spawn_hammer, input::{ActionState, InputAction}, button::spawn_any_button, item::ItemSetupSet, machine::spawn_machine, score::{SessionStats, Wallet}, spawner::{SpawnRandomItem, spawn_item_spawner}, states::GameplaySet, trash_bin::spawn_trash_bin};
 
 pub struct RoomPlugin;
 impl Plugin for RoomPlugin{
     fn build(&self, app: &mut App) {
+        app.add_message::<RoomUnlocked>();
+        app.add_message::<RoomChanged>();
         app.add_systems(Startup, (
             setup_rooms.after(ItemSetupSet),
-            place_ground_walls_ceiling.after(setup_rooms)
+            place_ground_walls_ceiling.after(setup_rooms),
+            place_room_locks.after(setup_rooms),
         ));
-        app.add_systems(Update, move_between_rooms);
+        app.add_systems(Update, (
+            check_unlock_conditions,
+            move_between_rooms,
+            remove_room_locks,
+        ).chain().in_set(GameplaySet));
     }
 }
 
@@ -21,6 +28,8 @@ pub const FLOOR_WIDTH: f32 =  128.0;
 pub const FLOOR_HEIGHT: f32 = 32.0;
 pub const WALL_WIDTH: f32 =  128.0;
 pub const CEILING_WIDTH: f32 =  128.0;
+const ROOM_LOCK_THICKNESS: f32 = 24.0;
+const PANTRY_ROOM_COST: f32 = 50.0;
 
 
 #[derive(Resource)]
@@ -30,20 +39,48 @@ pub struct RoomManager{
 }
 
 impl RoomManager{
-    fn try_moving(&mut self, dir: IVec2){
-        if self.rooms.contains_key(&(self.current_room_pos +dir)){
-            self.current_room_pos += dir;
-            println!("moved to {:?}", self.current_room_pos)
+    fn try_moving(&mut self, dir: IVec2, room_q: &Query<&mut Room>) -> bool{
+        let Some(room_entity) = self.rooms.get(&(self.current_room_pos +dir)) else {return false;};
+        if room_q.get(*room_entity).is_ok_and(|room| room.locked) {
+            debug!("room {:?} is locked", self.current_room_pos +dir);
+            return false;
         }
+        self.current_room_pos += dir;
+        true
     }
 }
 
+#[derive(Message, Debug, Clone)]
+pub struct RoomChanged{
+    pub from: IVec2,
+    pub to: IVec2,
+}
+
 #[derive(Component)]
-struct Room{
-    locked: bool,
+pub struct Room{
+    pub locked: bool,
+    pub unlock_condition: UnlockCondition,
     colliders: Vec<RoomCollider>
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnlockCondition{
+    Key,
+    Points(f32),
+    OrdersServed(u32),
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct RoomUnlocked{
+    pub room: Entity,
+    pub pos: IVec2,
+}
+
+#[derive(Component)]
+struct RoomLock{
+    room: Entity,
+}
+
 enum RoomCollider{
     Ground,
     Ceiling,
@@ -54,40 +91,62 @@ enum RoomCollider{
 
 fn setup_rooms(
     mut cmds: Commands,
-    item_catalog: Res<ItemCatalog>
 ){
     let mut rooms = HashMap::<IVec2, Entity>::new();
 
     let kitchen_room = cmds.spawn((
-        Room {locked: false, colliders: vec![RoomCollider::Ground, RoomCollider::Ceiling]},
+        Room {locked: false, unlock_condition: UnlockCondition::Key, colliders: vec![RoomCollider::Ground, RoomCollider::Ceiling]},
         Transform::from_xyz(0.0, 0.0, -10.0),
         Sprite::from_color(Color::linear_rgb(0.3, 0.0, 0.0), ROOM_SIZE),
     )).id();
     rooms.insert(ivec2(0, 0), kitchen_room);
     
     let counter_room = cmds.spawn((
-        Room {locked: false, colliders: vec![RoomCollider::Ground, RoomCollider::WallLeft, RoomCollider::Ceiling]},
+        Room {locked: false, unlock_condition: UnlockCondition::Key, colliders: vec![RoomCollider::Ground, RoomCollider::WallLeft, RoomCollider::Ceiling]},
         Transform::from_xyz( -ROOM_SIZE.x, 0.0, -10.0),
         Sprite::from_color(Color::linear_rgb(0.0, 0.3, 0.0), ROOM_SIZE),
     )).id();
     rooms.insert(ivec2(-1, 0), counter_room);
 
     let dispencer_room = cmds.spawn((
-        Room {locked: false, colliders: vec![RoomCollider::Ground, RoomCollider::WallRight, RoomCollider::Ceiling]},
+        Room {locked: false, unlock_condition: UnlockCondition::Key, colliders: vec![RoomCollider::Ground, RoomCollider::WallRight, RoomCollider::Ceiling]},
         Transform::from_xyz( ROOM_SIZE.x, 0.0, -10.0),
         Sprite::from_color(Color::linear_rgb(0.0, 0.3, 0.0), ROOM_SIZE),
     )).id();
     rooms.insert(ivec2(1, 0), dispencer_room);
 
+    let storage_room = cmds.spawn((
+        Room {locked: true, unlock_condition: UnlockCondition::Key, colliders: vec![RoomCollider::Ground, RoomCollider::WallLeft, RoomCollider::WallRight, RoomCollider::Ceiling]},
+        Transform::from_xyz(0.0, ROOM_SIZE.y, -10.0),
+        Sprite::from_color(Color::linear_rgb(0.0, 0.0, 0.3), ROOM_SIZE),
+    )).id();
+    rooms.insert(ivec2(0, 1), storage_room);
+
+    let pantry_room = cmds.spawn((
+        Room {locked: true, unlock_condition: UnlockCondition::Points(PANTRY_ROOM_COST), colliders: vec![RoomCollider::Ground, RoomCollider::WallRight, RoomCollider::Ceiling]},
+        Transform::from_xyz(ROOM_SIZE.x, ROOM_SIZE.y, -10.0),
+        Sprite::from_color(Color::linear_rgb(0.3, 0.2, 0.0), ROOM_SIZE),
+    )).id();
+    rooms.insert(ivec2(1, 1), pantry_room);
+
+    let cellar_room = cmds.spawn((
+        Room {locked: true, unlock_condition: UnlockCondition::OrdersServed(5), colliders: vec![RoomCollider::Ground, RoomCollider::WallLeft, RoomCollider::WallRight, RoomCollider::Ceiling]},
+        Transform::from_xyz(0.0, -ROOM_SIZE.y, -10.0),
+        Sprite::from_color(Color::linear_rgb(0.15, 0.1, 0.05), ROOM_SIZE),
+    )).id();
+    rooms.insert(ivec2(0, -1), cellar_room);
+
     let item_spawner = spawn_item_spawner(
         &mut cmds, 
         vec2(ROOM_SIZE.x, ROOM_SIZE.y/3.0), 
-        vec!["red".to_string(),"green".to_string(),"blue".to_string()], 
+        vec!["red".to_string(),"green".to_string(),"blue".to_string(),"ice".to_string(),"noodle".to_string(),"salad".to_string(),"ducky".to_string()], 
         vec2(0.0, -128.0)
     );
+    spawn_any_button(&mut cmds, vec2(ROOM_SIZE.x -64.0, ROOM_SIZE.y/3.0 -64.0), SpawnRandomItem(item_spawner));
 
     spawn_machine(&mut cmds, vec2(ROOM_SIZE.x/3.0, 16.0));
-    // spawn_item(&mut cmds, &item_catalog, vec2(ROOM_SIZE.x, 0.0), "yellow".to_string());
+    spawn_hammer(&mut cmds, vec2(-ROOM_SIZE.x/4.0, 0.0));
+    spawn_trash_bin(&mut cmds, vec2(-ROOM_SIZE.x/3.0, -ROOM_SIZE.y/2.0 +FLOOR_HEIGHT +FLOOR_WIDTH/2.0 +32.0));
 
     cmds.insert_resource(RoomManager{
         current_room_pos: ivec2(0, 0),
@@ -136,20 +195,105 @@ fn place_ground_walls_ceiling(
     }
 }
 
-fn move_between_rooms(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut room_manager: ResMut<RoomManager>
+fn place_room_locks(
+    mut cmds: Commands,
+    room_manager: Res<RoomManager>,
+    room_q: Query<&Room>,
 ){
-    if keyboard.just_pressed(KeyCode::KeyA){
-        room_manager.try_moving(ivec2(-1, 0));
+    for (room_pos, room_entity) in room_manager.rooms.iter(){
+        let Ok(room) = room_q.get(*room_entity) else {continue;};
+        if !room.locked {continue;}
+
+        for dir in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]{
+            if !room_manager.rooms.contains_key(&(*room_pos +dir)) {continue;}
+
+            // the lock sits on the edge facing the neighbouring room
+            let pos = (room_pos.as_vec2() +dir.as_vec2()*0.5) * ROOM_SIZE;
+            let size = if dir.x != 0 {
+                vec2(ROOM_LOCK_THICKNESS, ROOM_SIZE.y)
+            } else {
+                vec2(ROOM_SIZE.x, ROOM_LOCK_THICKNESS)
+            };
+            cmds.spawn((
+                RoomLock {room: *room_entity},
+                Sprite::from_color(Color::linear_rgb(0.8, 0.6, 0.1), size),
+                Transform::from_xyz(pos.x, pos.y, -5.0),
+            ));
+        }
     }
-    if keyboard.just_pressed(KeyCode::KeyD){
-        room_manager.try_moving(ivec2(1, 0));
+}
+
+fn check_unlock_conditions(
+    room_manager: Res<RoomManager>,
+    mut room_q: Query<&mut Room>,
+    session_stats: Res<SessionStats>,
+    mut room_unlocked_msgs: MessageWriter<RoomUnlocked>,
+){
+    for (room_pos, room_entity) in room_manager.rooms.iter(){
+        let Ok(mut room) = room_q.get_mut(*room_entity) else {continue;};
+        if !room.locked {continue;}
+
+        // keys and points are only spent when walking into the room
+        let condition_met = match room.unlock_condition {
+            UnlockCondition::Key | UnlockCondition::Points(_) => false,
+            UnlockCondition::OrdersServed(orders) => session_stats.orders_served >= orders,
+        };
+        if condition_met {
+            room.locked = false;
+            room_unlocked_msgs.write(RoomUnlocked {room: *room_entity, pos: *room_pos});
+        }
     }
-    if keyboard.just_pressed(KeyCode::KeyW){
-        room_manager.try_moving(ivec2(0, 1));
+}
+
+fn move_between_rooms(
+    action_state: Res<ActionState>,
+    mut room_manager: ResMut<RoomManager>,
+    mut room_q: Query<&mut Room>,
+    mut wallet: ResMut<Wallet>,
+    mut room_unlocked_msgs: MessageWriter<RoomUnlocked>,
+    mut room_changed_msgs: MessageWriter<RoomChanged>,
+){
+    let directions = [
+        (InputAction::RoomLeft, ivec2(-1, 0)),
+        (InputAction::RoomRight, ivec2(1, 0)),
+        (InputAction::RoomUp, ivec2(0, 1)),
+        (InputAction::RoomDown, ivec2(0, -1)),
+    ];
+    for (action, dir) in directions{
+        if !action_state.just_pressed(action) {continue;}
+
+        let target_pos = room_manager.current_room_pos +dir;
+        if let Some(&room_entity) = room_manager.rooms.get(&target_pos) {
+            if let Ok(mut room) = room_q.get_mut(room_entity) {
+                let unlocked = room.locked && match room.unlock_condition {
+                    UnlockCondition::Key => wallet.try_unlock(&mut room.locked),
+                    UnlockCondition::Points(cost) => wallet.try_spend_points(cost),
+                    UnlockCondition::OrdersServed(_) => false,
+                };
+                if unlocked {
+                    room.locked = false;
+                    room_unlocked_msgs.write(RoomUnlocked {room: room_entity, pos: target_pos});
+                }
+            }
+        }
+        let from = room_manager.current_room_pos;
+        if room_manager.try_moving(dir, &room_q) {
+            room_changed_msgs.write(RoomChanged { from, to: room_manager.current_room_pos });
+        }
     }
-    if keyboard.just_pressed(KeyCode::KeyS){
-        room_manager.try_moving(ivec2(0, -1));
+}
+
+fn remove_room_locks(
+    mut cmds: Commands,
+    mut room_unlocked_msgs: MessageReader<RoomUnlocked>,
+    room_lock_q: Query<(Entity, &RoomLock)>,
+){
+    for room_unlocked in room_unlocked_msgs.read(){
+        info!("room {:?} unlocked", room_unlocked.pos);
+        for (room_lock_entity, room_lock) in room_lock_q.iter(){
+            if room_lock.room == room_unlocked.room {
+                cmds.entity(room_lock_entity).despawn();
+            }
+        }
     }
 }
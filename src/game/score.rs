@@ -0,0 +1,134 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::game::{machine::{RecipeCompleted, RecipeFailed}, npc::{OrderDelivered, OrderFailed, Reward}, slap::Slapped, states::GameState};
+
+pub struct ScorePlugin;
+impl Plugin for ScorePlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Wallet>();
+        app.init_resource::<SessionStats>();
+        app.add_message::<RewardGranted>();
+        app.add_systems(OnEnter(GameState::InGame), reset_session);
+        app.add_systems(Update, (
+            apply_rewards,
+            count_failed_orders,
+            count_crafted_items,
+            count_failed_recipes,
+            count_slaps,
+        ));
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct Wallet{
+    pub points: f32,
+    pub keys: u32,
+}
+
+impl Wallet{
+    pub fn apply_reward(&mut self, reward: &Reward){
+        match reward {
+            Reward::Points(points) => self.points += points,
+            Reward::Key => self.keys += 1,
+        }
+    }
+
+    pub fn try_spend_points(&mut self, amount: f32) -> bool{
+        if self.points < amount {return false;}
+        self.points -= amount;
+        true
+    }
+
+    pub fn try_spend_key(&mut self) -> bool{
+        if self.keys == 0 {return false;}
+        self.keys -= 1;
+        true
+    }
+
+    // spends a key to open anything with a `locked` flag, e.g. Room.locked
+    pub fn try_unlock(&mut self, locked: &mut bool) -> bool{
+        if !*locked {return true;}
+        if !self.try_spend_key() {return false;}
+        *locked = false;
+        true
+    }
+}
+
+// a reward from anything other than a delivered order, e.g. dialogue
+#[derive(Message, Debug, Clone)]
+pub struct RewardGranted(pub Reward);
+
+#[derive(Resource, Default, Debug)]
+pub struct SessionStats{
+    pub orders_served: u32,
+    pub orders_failed: u32,
+    pub items_crafted: u32,
+    pub recipes_failed: u32,
+    pub points_earned: f32,
+    pub keys_earned: u32,
+    pub slaps: u32,
+    // how often each item was handed to a customer who wanted it
+    pub delivered_items: HashMap<String, u32>,
+}
+
+fn reset_session(
+    mut wallet: ResMut<Wallet>,
+    mut session_stats: ResMut<SessionStats>,
+){
+    *wallet = Wallet::default();
+    *session_stats = SessionStats::default();
+}
+
+fn grant_reward(wallet: &mut Wallet, session_stats: &mut SessionStats, reward: &Reward){
+    wallet.apply_reward(reward);
+    match reward {
+        Reward::Points(points) => session_stats.points_earned += points,
+        Reward::Key => session_stats.keys_earned += 1,
+    }
+}
+
+fn apply_rewards(
+    mut order_delivered_msgs: MessageReader<OrderDelivered>,
+    mut reward_granted_msgs: MessageReader<RewardGranted>,
+    mut wallet: ResMut<Wallet>,
+    mut session_stats: ResMut<SessionStats>,
+){
+    for order_delivered in order_delivered_msgs.read(){
+        grant_reward(&mut wallet, &mut session_stats, &order_delivered.reward);
+        session_stats.orders_served += 1;
+        *session_stats.delivered_items.entry(order_delivered.item.clone()).or_insert(0) += 1;
+    }
+    for reward_granted in reward_granted_msgs.read(){
+        grant_reward(&mut wallet, &mut session_stats, &reward_granted.0);
+    }
+}
+
+fn count_failed_orders(
+    mut order_failed_msgs: MessageReader<OrderFailed>,
+    mut session_stats: ResMut<SessionStats>,
+){
+    session_stats.orders_failed += order_failed_msgs.read().count() as u32;
+}
+
+fn count_crafted_items(
+    mut recipe_completed_msgs: MessageReader<RecipeCompleted>,
+    mut session_stats: ResMut<SessionStats>,
+){
+    for recipe_completed in recipe_completed_msgs.read(){
+        session_stats.items_crafted += recipe_completed.outputs.len() as u32;
+    }
+}
+
+fn count_failed_recipes(
+    mut recipe_failed_msgs: MessageReader<RecipeFailed>,
+    mut session_stats: ResMut<SessionStats>,
+){
+    session_stats.recipes_failed += recipe_failed_msgs.read().count() as u32;
+}
+
+fn count_slaps(
+    mut slapped_msgs: MessageReader<Slapped>,
+    mut session_stats: ResMut<SessionStats>,
+){
+    session_stats.slaps += slapped_msgs.read().count() as u32;
+}
@@ -0,0 +1,200 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{input::{InputAction, InputBindings, PendingRebind, SavedBinding}, labels::ItemLabelSettings, states::GamePhase};
+
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_settings);
+        app.add_systems(Update, save_settings);
+        app.add_systems(OnEnter(GamePhase::Paused), spawn_settings_menu);
+        app.add_systems(OnExit(GamePhase::Paused), despawn_settings_menu);
+        app.add_systems(Update, (handle_settings_buttons, update_settings_buttons).chain());
+    }
+}
+
+const SETTINGS_PATH: &str = "settings.ron";
+const SETTINGS_MENU_BACKGROUND_COLOR: Color = Color::srgba(0.05, 0.0, 0.1, 0.9);
+const SETTINGS_BUTTON_COLOR: Color = Color::srgb(0.2, 0.1, 0.3);
+const SETTINGS_BUTTON_HOVER_COLOR: Color = Color::srgb(0.4, 0.2, 0.5);
+
+// shown while the game is paused
+#[derive(Component)]
+struct SettingsMenu;
+
+#[derive(Component, Debug, Clone, Copy)]
+enum SettingsButton{
+    ItemLabels,
+    // waits for the next pressed button and binds it to the action
+    Rebind(InputAction),
+}
+
+impl SettingsButton{
+    fn text(&self, item_label_settings: &ItemLabelSettings, input_bindings: &InputBindings, pending_rebind: &PendingRebind) -> String{
+        match self {
+            SettingsButton::ItemLabels => format!("Item labels: {}", if item_label_settings.enabled {"on"} else {"off"}),
+            SettingsButton::Rebind(action) if pending_rebind.0 == Some(*action) => format!("{:?}: press a button, escape cancels", action),
+            SettingsButton::Rebind(action) => {
+                let bindings: Vec<&str> = input_bindings.get(*action).iter().map(|binding| binding.name()).collect();
+                format!("{:?}: {}", action, bindings.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct SettingsFile{
+    #[serde(default)]
+    bindings: Vec<(InputAction, Vec<SavedBinding>)>,
+    #[serde(default = "default_show_item_labels")]
+    show_item_labels: bool,
+}
+
+fn default_show_item_labels() -> bool {true}
+
+fn load_settings(
+    mut input_bindings: ResMut<InputBindings>,
+    mut item_label_settings: ResMut<ItemLabelSettings>,
+){
+    let Some(contents) = read_settings() else {return};
+    match ron::de::from_str::<SettingsFile>(&contents) {
+        Ok(settings_file) => {
+            input_bindings.apply_saved(&settings_file.bindings);
+            item_label_settings.enabled = settings_file.show_item_labels;
+            info!("loaded settings from {}", SETTINGS_PATH);
+        }
+        Err(error) => warn!("could not parse {}, using defaults: {}", SETTINGS_PATH, error),
+    }
+}
+
+fn save_settings(
+    input_bindings: Res<InputBindings>,
+    item_label_settings: Res<ItemLabelSettings>,
+){
+    // skip the first frame, loading the settings counts as a change too
+    let bindings_changed = input_bindings.is_changed() && !input_bindings.is_added();
+    let labels_changed = item_label_settings.is_changed() && !item_label_settings.is_added();
+    if !bindings_changed && !labels_changed {return;}
+
+    let settings_file = SettingsFile {
+        bindings: input_bindings.to_saved(),
+        show_item_labels: item_label_settings.enabled,
+    };
+    match ron::ser::to_string_pretty(&settings_file, ron::ser::PrettyConfig::default()) {
+        Ok(contents) => write_settings(&contents),
+        Err(error) => warn!("could not serialize settings: {}", error),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_settings() -> Option<String>{
+    std::fs::read_to_string(SETTINGS_PATH).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_settings(contents: &str){
+    if let Err(error) = std::fs::write(SETTINGS_PATH, contents) {
+        warn!("could not write {}: {}", SETTINGS_PATH, error);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_settings() -> Option<String>{
+    let storage = web_sys::window()?.local_storage().ok()??;
+    storage.get_item(SETTINGS_PATH).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_settings(contents: &str){
+    let Some(Ok(Some(storage))) = web_sys::window().map(|window| window.local_storage()) else {return};
+    if storage.set_item(SETTINGS_PATH, contents).is_err() {
+        warn!("could not write {} to local storage", SETTINGS_PATH);
+    }
+}
+
+fn spawn_settings_menu(
+    mut cmds: Commands,
+    item_label_settings: Res<ItemLabelSettings>,
+    input_bindings: Res<InputBindings>,
+    pending_rebind: Res<PendingRebind>,
+){
+    cmds.spawn((
+        SettingsMenu,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            right: Val::Px(16.0),
+            padding: UiRect::all(Val::Px(12.0)),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        BackgroundColor(SETTINGS_MENU_BACKGROUND_COLOR),
+    )).with_children(|parent| {
+        parent.spawn(Text::new("Paused"));
+        let mut settings_buttons = vec![SettingsButton::ItemLabels];
+        settings_buttons.extend(InputAction::ALL.map(SettingsButton::Rebind));
+        for settings_button in settings_buttons{
+            if matches!(settings_button, SettingsButton::Rebind(InputAction::Grab)) {
+                parent.spawn(Text::new("Controls"));
+            }
+            parent.spawn((
+                settings_button,
+                Button,
+                Node {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                    ..default()
+                },
+                BackgroundColor(SETTINGS_BUTTON_COLOR),
+                children![Text::new(settings_button.text(&item_label_settings, &input_bindings, &pending_rebind))],
+            ));
+        }
+    });
+}
+
+fn despawn_settings_menu(
+    mut cmds: Commands,
+    settings_menu_q: Query<Entity, With<SettingsMenu>>,
+    mut pending_rebind: ResMut<PendingRebind>,
+){
+    pending_rebind.0 = None;
+    for settings_menu in settings_menu_q.iter(){
+        cmds.entity(settings_menu).despawn();
+    }
+}
+
+fn handle_settings_buttons(
+    mut button_q: Query<(&Interaction, &SettingsButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut item_label_settings: ResMut<ItemLabelSettings>,
+    mut pending_rebind: ResMut<PendingRebind>,
+){
+    for (interaction, settings_button, mut background_color) in button_q.iter_mut(){
+        background_color.0 = match interaction {
+            Interaction::Hovered => SETTINGS_BUTTON_HOVER_COLOR,
+            _ => SETTINGS_BUTTON_COLOR,
+        };
+        if *interaction != Interaction::Pressed {continue;}
+        match settings_button {
+            SettingsButton::ItemLabels => item_label_settings.enabled = !item_label_settings.enabled,
+            SettingsButton::Rebind(action) => pending_rebind.0 = Some(*action),
+        }
+    }
+}
+
+// keeps the button text in sync, the labels can also be toggled with their key
+fn update_settings_buttons(
+    item_label_settings: Res<ItemLabelSettings>,
+    input_bindings: Res<InputBindings>,
+    pending_rebind: Res<PendingRebind>,
+    button_q: Query<(&SettingsButton, &Children)>,
+    mut text_q: Query<&mut Text>,
+){
+    if !item_label_settings.is_changed() && !input_bindings.is_changed() && !pending_rebind.is_changed() {return;}
+    for (settings_button, children) in button_q.iter(){
+        for child in children{
+            let Ok(mut text) = text_q.get_mut(*child) else {continue};
+            text.0 = settings_button.text(&item_label_settings, &input_bindings, &pending_rebind);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::game::{hand::{Hand, HandUpdateSet}, input::{ActionState, InputAction, WorldInputSet}, npc::NPC, states::GameplaySet};
+
+pub struct SlapPlugin;
+impl Plugin for SlapPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_message::<Slapped>();
+        app.add_systems(Update, handle_slapping.after(HandUpdateSet).in_set(GameplaySet).in_set(WorldInputSet));
+    }
+}
+
+// hand speed in pixels per second that counts as a swipe while the slap button is held
+const SLAP_SWIPE_SPEED: f32 = 2500.0;
+// strength used when slapping with the button while the hand is barely moving
+const SLAP_MIN_STRENGTH: f32 = 1000.0;
+const SLAP_RADIUS: f32 = 16.0;
+const SLAP_COOLDOWN_SECS: f32 = 0.3;
+const SLAP_IMPULSE_PER_STRENGTH: f32 = 0.3;
+
+#[derive(Message, Debug, Clone)]
+pub struct Slapped{
+    pub target: Entity,
+    pub strength: f32,
+    pub direction: Vec2,
+}
+
+fn handle_slapping(
+    mut cmds: Commands,
+    time: Res<Time>,
+    action_state: Res<ActionState>,
+    rapier_context: ReadRapierContext,
+    hand_q: Query<(Entity, &Hand, &Transform)>,
+    rigidbody_q: Query<&RigidBody>,
+    npc_q: Query<(), With<NPC>>,
+    mut slapped_msgs: MessageWriter<Slapped>,
+    mut last_slap_secs: Local<Option<f32>>,
+){
+    let Ok(rapier_context) = rapier_context.single() else {return};
+    let Ok((hand_entity, hand, hand_transform)) = hand_q.single() else {return};
+    if hand.is_grabbing {return;}
+
+    let now = time.elapsed_secs();
+    if last_slap_secs.is_some_and(|last_slap_secs| now -last_slap_secs < SLAP_COOLDOWN_SECS) {return;}
+
+    // moving the hand fast on its own never slaps, the player has to mean it
+    if !action_state.pressed(InputAction::Slap) {return;}
+    let speed = hand.velocity.length();
+    let strength = if action_state.just_pressed(InputAction::Slap) {
+        speed.max(SLAP_MIN_STRENGTH)
+    } else if speed >= SLAP_SWIPE_SPEED {
+        speed
+    } else {
+        return;
+    };
+    let direction = hand.velocity.normalize_or(Vec2::Y);
+
+    let mut targets = Vec::new();
+    rapier_context.intersect_shape(
+        hand_transform.translation.truncate(),
+        0.0,
+        Collider::ball(SLAP_RADIUS).raw.make_mut(),
+        QueryFilter::default(),
+        |entity| {
+            let is_dynamic = matches!(rigidbody_q.get(entity), Ok(RigidBody::Dynamic));
+            if entity != hand_entity && (is_dynamic || npc_q.contains(entity)) {
+                targets.push(entity);
+            }
+            true
+        }
+    );
+    if targets.is_empty() {return;}
+    *last_slap_secs = Some(now);
+
+    for target in targets{
+        if matches!(rigidbody_q.get(target), Ok(RigidBody::Dynamic)) {
+            cmds.entity(target).insert(ExternalImpulse {
+                impulse: direction * strength * SLAP_IMPULSE_PER_STRENGTH,
+                torque_impulse: 0.0,
+            });
+        }
+        slapped_msgs.write(Slapped {target, strength, direction});
+    }
+}
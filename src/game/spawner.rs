@@ -2,24 +2,25 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::Collider;
 use rand::seq::IndexedRandom;
 
-use crate::game::item::{self, ItemCatalog, spawn_item};
+use crate::game::{item::{self, ItemCatalog, spawn_item}, states::GameplaySet};
 
 pub struct SpawnerPlugin;
 impl Plugin for SpawnerPlugin{
     fn build(&self, app: &mut App) {
         app.add_message::<SpawnRandomItem>();
-        app.add_systems(Update, handle_spawning);
+        app.add_systems(Update, handle_spawning.in_set(GameplaySet));
     }
 }
 
 #[derive(Component)]
-struct ItemSpawner{
-    item_list: Vec<String>,
+pub struct ItemSpawner{
+    pub item_list: Vec<String>,
     offset: Vec2,
 }
 
-#[derive(Message)]
-struct SpawnRandomItem(Entity);
+// makes the given item spawner drop one of its items, e.g. from a dispenser button
+#[derive(Message, Clone)]
+pub struct SpawnRandomItem(pub Entity);
 
 
 pub fn spawn_item_spawner(
@@ -1,14 +1,26 @@
 
 use bevy::prelude::*;
+use bevy_rapier2d::prelude::RapierConfiguration;
+
+use crate::game::input::{ActionState, InputAction};
 
 pub struct StatesPlugin;
 impl Plugin for StatesPlugin{
     fn build(&self, app: &mut App) {
         app.init_state::<GameState>();
         app.init_state::<GamePhase>();
+        app.configure_sets(Update, GameplaySet.run_if(in_state(GamePhase::Playing)));
+        app.configure_sets(PostUpdate, GameplaySet.run_if(in_state(GamePhase::Playing)));
+        app.add_systems(Update, toggle_pause.run_if(in_state(GameState::InGame)));
+        app.add_systems(OnEnter(GamePhase::Paused), pause_physics);
+        app.add_systems(OnExit(GamePhase::Paused), resume_physics);
     }
 }
 
+// everything that moves the kitchen along, frozen while the game is paused
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameplaySet;
+
 #[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum GameState {
     Splash,
@@ -24,3 +36,30 @@ pub enum GamePhase {
     Paused,
 }
 
+fn toggle_pause(
+    action_state: Res<ActionState>,
+    game_phase: Res<State<GamePhase>>,
+    mut next_game_phase: ResMut<NextState<GamePhase>>,
+){
+    if !action_state.just_pressed(InputAction::Pause) {return;}
+    next_game_phase.set(match game_phase.get() {
+        GamePhase::Playing => GamePhase::Paused,
+        GamePhase::Paused => GamePhase::Playing,
+    });
+}
+
+fn pause_physics(
+    mut rapier_config_q: Query<&mut RapierConfiguration>,
+){
+    for mut rapier_config in rapier_config_q.iter_mut(){
+        rapier_config.physics_pipeline_active = false;
+    }
+}
+
+fn resume_physics(
+    mut rapier_config_q: Query<&mut RapierConfiguration>,
+){
+    for mut rapier_config in rapier_config_q.iter_mut(){
+        rapier_config.physics_pipeline_active = true;
+    }
+}
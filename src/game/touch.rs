@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+
+use crate::game::input::{ActionUpdateSet, InputAction, VirtualActions};
+
+pub struct TouchInputPlugin;
+impl Plugin for TouchInputPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchState>();
+        app.add_systems(PreUpdate, handle_touches.before(ActionUpdateSet));
+    }
+}
+
+// taps this close to the screen edge switch rooms instead of grabbing
+const TOUCH_EDGE_MARGIN: f32 = 48.0;
+const TOUCH_TAP_MAX_SECS: f32 = 0.3;
+const TOUCH_TAP_MAX_DISTANCE: f32 = 16.0;
+const TOUCH_SWIPE_MIN_DISTANCE: f32 = 80.0;
+
+#[derive(Resource, Default, Debug)]
+pub struct TouchState{
+    // screen position of the finger that drives the hand
+    pub position: Option<Vec2>,
+    gesture: TouchGesture,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+enum TouchGesture{
+    #[default]
+    None,
+    Hand { edge: Option<InputAction>, start_secs: f32 },
+    Swipe { fired: bool },
+}
+
+fn edge_action(pos: Vec2, window: &Window) -> Option<InputAction>{
+    if pos.x < TOUCH_EDGE_MARGIN {
+        Some(InputAction::RoomLeft)
+    } else if pos.x > window.width() -TOUCH_EDGE_MARGIN {
+        Some(InputAction::RoomRight)
+    } else if pos.y < TOUCH_EDGE_MARGIN {
+        Some(InputAction::RoomUp)
+    } else if pos.y > window.height() -TOUCH_EDGE_MARGIN {
+        Some(InputAction::RoomDown)
+    } else {
+        None
+    }
+}
+
+// swiping drags the world along, so a swipe to the left reveals the room to the right
+fn swipe_action(screen_delta: Vec2) -> InputAction{
+    if screen_delta.x.abs() > screen_delta.y.abs() {
+        if screen_delta.x < 0.0 {InputAction::RoomRight} else {InputAction::RoomLeft}
+    } else if screen_delta.y < 0.0 {
+        InputAction::RoomDown
+    } else {
+        InputAction::RoomUp
+    }
+}
+
+fn handle_touches(
+    time: Res<Time>,
+    touches: Res<Touches>,
+    windows: Query<&Window>,
+    mut touch_state: ResMut<TouchState>,
+    mut virtual_actions: ResMut<VirtualActions>,
+){
+    let Ok(window) = windows.single() else {return};
+    let now = time.elapsed_secs();
+    virtual_actions.0.clear();
+
+    let pressed: Vec<&Touch> = touches.iter().collect();
+    match pressed.as_slice() {
+        [] => {
+            if let TouchGesture::Hand { edge: Some(edge), start_secs } = touch_state.gesture {
+                let is_tap = touches.iter_just_released()
+                    .any(|touch| now -start_secs < TOUCH_TAP_MAX_SECS && touch.distance().length() < TOUCH_TAP_MAX_DISTANCE);
+                if is_tap {
+                    virtual_actions.0.insert(edge);
+                }
+            }
+            touch_state.gesture = TouchGesture::None;
+        }
+        [touch] => {
+            // the hand only moves to the finger in PostUpdate, so grabbing waits a frame
+            let just_touched = touch_state.gesture == TouchGesture::None;
+            if just_touched {
+                touch_state.gesture = TouchGesture::Hand {
+                    edge: edge_action(touch.start_position(), window),
+                    start_secs: now,
+                };
+            }
+            // dragging away from the edge turns the tap into a normal grab
+            if let TouchGesture::Hand { edge: Some(_), start_secs } = touch_state.gesture {
+                if touch.distance().length() >= TOUCH_TAP_MAX_DISTANCE {
+                    touch_state.gesture = TouchGesture::Hand { edge: None, start_secs };
+                }
+            }
+            if !just_touched && matches!(touch_state.gesture, TouchGesture::Hand { edge: None, .. }) {
+                virtual_actions.0.insert(InputAction::Grab);
+            }
+            touch_state.position = Some(touch.position());
+        }
+        [first, second, ..] => {
+            if !matches!(touch_state.gesture, TouchGesture::Swipe { .. }) {
+                touch_state.gesture = TouchGesture::Swipe { fired: false };
+            }
+            if touch_state.gesture == (TouchGesture::Swipe { fired: false }) {
+                let screen_delta = (first.distance() +second.distance()) / 2.0;
+                if screen_delta.length() >= TOUCH_SWIPE_MIN_DISTANCE {
+                    virtual_actions.0.insert(swipe_action(screen_delta));
+                    touch_state.gesture = TouchGesture::Swipe { fired: true };
+                }
+            }
+        }
+    }
+}
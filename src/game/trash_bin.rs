@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{Collider, Sensor};
+
+use crate::game::interactable::{AcceptsItems, Interactable, ItemDropped};
+
+pub struct TrashBinPlugin;
+impl Plugin for TrashBinPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_observer(on_item_dropped);
+    }
+}
+
+const TRASH_BIN_SIZE: Vec2 = vec2(64.0, 64.0);
+
+// swallows anything dropped into it
+#[derive(Component)]
+pub struct TrashBin;
+
+pub fn spawn_trash_bin(cmds: &mut Commands, pos: Vec2) -> Entity{
+    cmds.spawn((
+        TrashBin,
+        Interactable,
+        AcceptsItems::Any,
+        Sprite::from_color(Color::linear_rgb(0.2, 0.25, 0.2), TRASH_BIN_SIZE),
+        Transform::from_xyz(pos.x, pos.y, 0.0),
+        Collider::cuboid(TRASH_BIN_SIZE.x/2.0, TRASH_BIN_SIZE.y/2.0),
+        Sensor,
+    )).id()
+}
+
+fn on_item_dropped(
+    item_dropped: On<ItemDropped>,
+    mut cmds: Commands,
+    trash_bin_q: Query<(), With<TrashBin>>,
+){
+    if !trash_bin_q.contains(item_dropped.target) {return;}
+    cmds.entity(item_dropped.item).despawn();
+}